@@ -0,0 +1,118 @@
+//! Benchmarks for reading history files. Every benchmark runs on histories of
+//! growing size and reports its throughput, which stays the same from one size
+//! to the next when reading takes linear time.
+//!
+//! Run them with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hui::history::{self, Command, Dedup, Format, HistorySource, Source};
+use hui::ignore::Ignore;
+use std::fs;
+use std::path::PathBuf;
+
+const SIZES: [usize; 4] = [10_000, 40_000, 160_000, 640_000];
+
+/// `count` commands like those in a real history: mostly short, some repeated,
+/// a few spanning several lines and some with characters zsh metafies.
+fn commands(count: usize) -> Vec<Command> {
+    const WORDS: [&str; 12] = [
+        "git",
+        "status",
+        "cargo",
+        "test",
+        "ls",
+        "-la",
+        "cd",
+        "..",
+        "docker",
+        "run",
+        "grüße",
+        "日本語",
+    ];
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = |below: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % below as u64) as usize
+    };
+    (0..count)
+        .map(|i| {
+            let words: Vec<&str> = (0..1 + random(6))
+                .map(|_| WORDS[random(WORDS.len())])
+                .collect();
+            let mut command = words.join(" ");
+            if i % 500 == 0 {
+                command.push_str(" \\\n  --and-a-second-line");
+            }
+            Command::new(command, Some(1_600_000_000 + i as u64))
+        })
+        .collect()
+}
+
+fn file(format: Format, count: usize) -> Vec<u8> {
+    let mut contents = Vec::new();
+    history::write(format, &commands(count), &mut contents).unwrap();
+    contents
+}
+
+fn parse(c: &mut Criterion) {
+    for format in [Format::Zsh, Format::Bash, Format::Fish] {
+        let mut group = c.benchmark_group(format!("parse/{}", format.name()));
+        group.sample_size(10);
+        for count in SIZES {
+            let contents = file(format, count);
+            group.throughput(Throughput::Bytes(contents.len() as u64));
+            group.bench_with_input(
+                BenchmarkId::from_parameter(count),
+                &contents,
+                |b, contents| b.iter(|| history::parse(format, contents.clone()).unwrap()),
+            );
+        }
+        group.finish();
+    }
+}
+
+fn read_backwards(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_backwards/zsh");
+    group.sample_size(10);
+    for count in SIZES {
+        let path: PathBuf = std::env::temp_dir().join(format!("hui-bench-{}", count));
+        let contents = file(Format::Zsh, count);
+        fs::write(&path, &contents).unwrap();
+        let source = Source::parse(&format!("zsh:{}", path.display()));
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter(|| {
+                let mut history = Vec::new();
+                source
+                    .read_backwards(contents.len() as u64, &mut |mut older| {
+                        older.append(&mut history);
+                        history = older;
+                    })
+                    .unwrap();
+                history
+            })
+        });
+        fs::remove_file(path).unwrap();
+    }
+    group.finish();
+}
+
+fn merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merge");
+    group.sample_size(10);
+    for count in SIZES {
+        let histories = vec![commands(count / 2), commands(count / 2)];
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &histories,
+            |b, histories| b.iter(|| history::merge(histories, Dedup::Latest, &Ignore::default())),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, read_backwards, merge);
+criterion_main!(benches);
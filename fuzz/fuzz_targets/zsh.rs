@@ -0,0 +1,17 @@
+//! Reads arbitrary bytes as a zsh history file, which must not panic, then
+//! writes the commands read and reads them back, which must give the same
+//! commands.
+//!
+//! Run it with `cargo +nightly fuzz run zsh`.
+
+#![no_main]
+
+use hui::history::{self, Format};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|contents: &[u8]| {
+    let commands = history::parse(Format::Zsh, contents.to_vec()).unwrap();
+    let mut written = Vec::new();
+    history::write(Format::Zsh, &commands, &mut written).unwrap();
+    assert_eq!(history::parse(Format::Zsh, written).unwrap(), commands);
+});
@@ -0,0 +1,90 @@
+use clap::{Parser, Subcommand};
+use hui::export::ExportFormat;
+use hui::import::Tool;
+use hui::period::{self, Period};
+use hui::query::SearchArgs;
+use std::path::PathBuf;
+
+/// A visual alternative to `history`.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Start in the favorites view, or only search favorites
+    #[arg(long, visible_alias = "favourites", global = true)]
+    pub favorites: bool,
+    /// Only commands run since then: today, yesterday, a weekday, a date like
+    /// 2024-03-05 or 2024-03, or a time ago like 2w
+    #[arg(long, global = true, value_parser = period::since)]
+    pub since: Option<u64>,
+    /// Only commands run until then, with the same dates as --since
+    #[arg(long, global = true, value_parser = period::until)]
+    pub until: Option<u64>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+impl Args {
+    /// The period `--since` and `--until` narrow the history to.
+    pub fn period(&self) -> Period {
+        Period {
+            since: self.since,
+            until: self.until,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Import the history of another tool, to be searched along with your shell history
+    Import {
+        tool: Tool,
+        /// The tool's database or history file, if it isn't in the default location
+        path: Option<PathBuf>,
+    },
+    /// Print the history entries matching a query, best match first
+    Search(SearchArgs),
+    /// Show statistics about the commands you run
+    Stats {
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write the merged history to stdout, oldest entry first
+    Export {
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+    },
+    /// Share your history with other machines through a directory synced between them
+    Sync {
+        /// The synced directory; remembered, so only needed the first time
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// The name of this machine, if not its host name
+        #[arg(long)]
+        host: Option<String>,
+        /// Encrypt what this machine adds, with the key file or $HUI_SYNC_PASSPHRASE
+        #[arg(long)]
+        encrypt: bool,
+        /// A key file to encrypt and decrypt with; remembered like --dir
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+        /// Create a key file with a new random key and use it like --key-file
+        #[arg(long, conflicts_with = "key_file")]
+        new_key_file: Option<PathBuf>,
+        /// Create the salt $HUI_SYNC_PASSPHRASE needs in the synced directory; only on the first machine
+        #[arg(long, conflicts_with_all = ["key_file", "new_key_file"])]
+        new_salt: bool,
+    },
+    /// Manage the key hui's data files are encrypted with
+    Key {
+        #[command(subcommand)]
+        command: KeyCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyCommand {
+    /// Encrypt every data file with a new key, starting to encrypt them if they aren't yet
+    Rotate,
+}
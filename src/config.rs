@@ -0,0 +1,64 @@
+use hui::history::Dedup;
+use std::env;
+
+/// Settings read from `HUI_*` environment variables, the same way `HUI_TERM` is.
+pub struct Config {
+    /// How many entries a single mouse wheel step moves the selection (`HUI_SCROLL_LINES`).
+    pub scroll_lines: usize,
+    /// Where accepted commands go (`HUI_OUTPUT`).
+    pub output: Output,
+    /// Which runs of a repeated command are listed (`HUI_DEDUP`).
+    pub dedup: Dedup,
+    /// The colors commands are highlighted with (`HUI_THEME`).
+    pub theme: Palette,
+}
+
+/// Where an accepted command goes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// Copy it to the clipboard (`clipboard`, the default).
+    Clipboard,
+    /// Print only the command on stdout (`stdout`), so that shell
+    /// integrations can put it on the command line.
+    Stdout,
+}
+
+/// A set of colors for highlighting commands.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    /// For terminals with a dark background (`dark`, the default).
+    Dark,
+    /// For terminals with a light background (`light`).
+    Light,
+    /// No colors, only bold for the command name and matches (`none`).
+    Plain,
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        Config {
+            scroll_lines: parse_var("HUI_SCROLL_LINES")
+                .filter(|lines| *lines > 0)
+                .unwrap_or(1),
+            output: match env::var("HUI_OUTPUT").as_deref() {
+                Ok("stdout") => Output::Stdout,
+                _ => Output::Clipboard,
+            },
+            dedup: env::var("HUI_DEDUP")
+                .ok()
+                .and_then(|name| Dedup::parse(name.trim()))
+                .unwrap_or(Dedup::Latest),
+            theme: match env::var("HUI_THEME").as_deref().map(str::trim) {
+                Ok("light") => Palette::Light,
+                Ok("none") => Palette::Plain,
+                _ => Palette::Dark,
+            },
+        }
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+}
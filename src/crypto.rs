@@ -0,0 +1,204 @@
+//! Encryption of what hui writes, with XChaCha20-Poly1305. A key is either
+//! random, kept in a key file, or derived from a passphrase with Argon2id.
+//!
+//! Encrypted data is the random nonce followed by the ciphertext, so the
+//! same data encrypts differently every time, and changing a single byte of
+//! it makes decrypting it fail rather than give something else.
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const NONCE_LEN: usize = 24;
+
+/// How long salts for deriving keys from passphrases are.
+pub const SALT_LEN: usize = 16;
+
+/// A key to encrypt and decrypt with.
+#[derive(Clone)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    /// A new random key.
+    pub fn generate() -> Key {
+        Key(XChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// Derives the key for `passphrase`, which is always the same for the
+    /// same `salt`.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> io::Result<Key> {
+        let mut key = [0; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+        Ok(Key(key))
+    }
+
+    /// Reads a key file, which holds the key in base64. Like ssh does with
+    /// private keys, a key file others can read is refused.
+    pub fn read(path: &Path) -> io::Result<Key> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "{}: others can access this key file, run chmod 600 on it",
+                        path.display()
+                    ),
+                ));
+            }
+        }
+        let contents = fs::read_to_string(path)?;
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: not a hui key file", path.display()),
+            )
+        };
+        let bytes = BASE64.decode(contents.trim()).map_err(|_| invalid())?;
+        Ok(Key(bytes.try_into().map_err(|_| invalid())?))
+    }
+
+    /// Writes the key to a new key file that only its owner can read.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        writeln!(file, "{}", BASE64.encode(self.0))?;
+        file.sync_all()
+    }
+
+    /// Encrypts `plaintext` with a random nonce, which is put before the ciphertext.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let cipher = XChaCha20Poly1305::new(&self.0.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(
+            cipher
+                .encrypt(&nonce, plaintext)
+                .expect("encrypting in memory doesn't fail"),
+        );
+        encrypted
+    }
+
+    /// Decrypts what `encrypt` encrypted with the same key. Fails when the key
+    /// is another one or the data was changed.
+    pub fn decrypt(&self, encrypted: &[u8]) -> io::Result<Vec<u8>> {
+        let cipher = XChaCha20Poly1305::new(&self.0.into());
+        let wrong = || io::Error::new(io::ErrorKind::InvalidData, "wrong key or damaged data");
+        if encrypted.len() < NONCE_LEN {
+            return Err(wrong());
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| wrong())
+    }
+
+    /// Encrypts `plaintext` into a line of text, without a line break.
+    pub fn encrypt_line(&self, plaintext: &[u8]) -> String {
+        BASE64.encode(self.encrypt(plaintext))
+    }
+
+    /// Decrypts a line `encrypt_line` wrote.
+    pub fn decrypt_line(&self, line: &str) -> io::Result<Vec<u8>> {
+        let encrypted = BASE64
+            .decode(line.trim())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.decrypt(&encrypted)
+    }
+}
+
+/// A new random salt for `Key::from_passphrase`, in base64.
+pub fn generate_salt() -> String {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    BASE64.encode(salt)
+}
+
+/// Decodes a salt `generate_salt` made.
+pub fn decode_salt(salt: &str) -> io::Result<Vec<u8>> {
+    BASE64
+        .decode(salt.trim())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// A file keeping a salt, which needn't be secret, for every run to derive
+/// the same key from a passphrase.
+#[derive(Serialize, Deserialize)]
+struct SaltFile {
+    salt: String,
+}
+
+/// Reads the salt kept in the file at `path`, first writing a new one there
+/// when there is none yet.
+pub fn read_salt(path: &Path) -> io::Result<Vec<u8>> {
+    let salt = match fs::read(path) {
+        Ok(contents) => {
+            serde_json::from_slice::<SaltFile>(&contents)
+                .map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: {}", path.display(), err),
+                    )
+                })?
+                .salt
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let salt = generate_salt();
+            write_salt(path, &salt)?;
+            salt
+        }
+        Err(err) => return Err(err),
+    };
+    decode_salt(&salt)
+}
+
+/// Writes `salt`, made by `generate_salt`, to the file at `path`.
+pub fn write_salt(path: &Path, salt: &str) -> io::Result<()> {
+    let file = SaltFile {
+        salt: salt.to_string(),
+    };
+    fs::write(path, serde_json::to_vec_pretty(&file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_same_key_decrypts() {
+        let key = Key::generate();
+        let encrypted = key.encrypt_line(b"git push --force");
+        assert_ne!(key.encrypt_line(b"git push --force"), encrypted);
+        assert_eq!(key.decrypt_line(&encrypted).unwrap(), b"git push --force");
+        assert!(Key::generate().decrypt_line(&encrypted).is_err());
+
+        let mut damaged = key.encrypt(b"ls");
+        *damaged.last_mut().unwrap() ^= 1;
+        assert!(key.decrypt(&damaged).is_err());
+        assert!(key.decrypt(b"short").is_err());
+    }
+
+    #[test]
+    fn passphrases_derive_the_same_key_for_the_same_salt() {
+        let salt = decode_salt(&generate_salt()).unwrap();
+        let key = Key::from_passphrase("correct horse", &salt).unwrap();
+        let encrypted = key.encrypt(b"ls");
+        let again = Key::from_passphrase("correct horse", &salt).unwrap();
+        assert_eq!(again.decrypt(&encrypted).unwrap(), b"ls");
+        let other = Key::from_passphrase("battery staple", &salt).unwrap();
+        assert!(other.decrypt(&encrypted).is_err());
+    }
+}
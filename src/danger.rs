@@ -0,0 +1,93 @@
+//! The danger list: commands that hui asks about before running them,
+//! like `rm -rf` or `git push --force`.
+
+use crate::store;
+use regex::Regex;
+use serde::Deserialize;
+use std::io;
+use std::ops::Range;
+
+/// Name of the data file the danger list is read from.
+pub const FILE: &str = "danger.json";
+
+/// What makes a command risky to run without a second look, unless the
+/// data file leaves these out.
+pub const DEFAULTS: [&str; 6] = [
+    r"\brm\s+(-\w*[rR]\w*f|-\w*f\w*[rR]|-[rR]\s+-f|-f\s+-[rR])\w*",
+    r"\bgit\s+push\b.*?(--force\b|--force-with-lease\b|\s-f\b)",
+    r"(?i)\bdrop\s+(table|database|schema)\b",
+    r"\bkubectl\s+delete\b",
+    r"\bdd\b.*?\bof=\S*",
+    r"\bmkfs(\.\w+)?\b",
+];
+
+/// The danger list as written in the data file:
+///
+/// ```json
+/// {
+///   "regex": ["terraform destroy", "^shutdown\\b"],
+///   "defaults": true
+/// }
+/// ```
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    /// Commands matching any of these regexes are dangerous.
+    pub regex: Vec<String>,
+    /// Whether `DEFAULTS` are dangerous too.
+    pub defaults: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            regex: Vec::new(),
+            defaults: true,
+        }
+    }
+}
+
+/// The compiled danger list. The default is `DEFAULTS`.
+pub struct Danger {
+    regex: Vec<Regex>,
+}
+
+impl Default for Danger {
+    fn default() -> Danger {
+        Danger::new(Rules::default()).expect("the default danger list is valid")
+    }
+}
+
+impl Danger {
+    /// Compiles the danger list written as `rules`.
+    pub fn new(rules: Rules) -> Result<Danger, regex::Error> {
+        let defaults = if rules.defaults { &DEFAULTS[..] } else { &[] };
+        let regex = defaults
+            .iter()
+            .copied()
+            .chain(rules.regex.iter().map(String::as_str))
+            .map(Regex::new)
+            .collect::<Result<_, _>>()?;
+        Ok(Danger { regex })
+    }
+
+    /// Reads the danger list from the data file. Without one, the defaults apply.
+    pub fn load() -> io::Result<Danger> {
+        store::load_rules(FILE, |rules: &Rules| &rules.regex, Danger::new)
+    }
+
+    /// Returns the risky part of `command`, the first one when several rules
+    /// match, or `None` when it is safe to run.
+    pub fn find(&self, command: &str) -> Option<Range<usize>> {
+        self.regex
+            .iter()
+            .filter_map(|regex| regex.find(command))
+            .map(|found| found.range())
+            .min_by_key(|range| range.start)
+    }
+
+    /// Whether any rule matches `command`.
+    pub fn is_dangerous(&self, command: &str) -> bool {
+        self.regex.iter().any(|regex| regex.is_match(command))
+    }
+}
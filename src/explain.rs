@@ -0,0 +1,787 @@
+//! What the programs and flags of a command do, read from the man pages and
+//! tldr pages installed locally. Nothing is fetched over the network.
+//!
+//! Man pages are found with `man -w` when `man` is installed, and in the
+//! usual man directories and `$MANPATH` otherwise. Their roff source is read
+//! directly, gzipped or not, for both the man(7) macros most Linux pages are
+//! written in and the mdoc(7) ones of the BSDs and macOS. Flags a man page
+//! doesn't describe are looked up in the examples of the tldr page, when a
+//! tldr client keeps a cache of them.
+
+use crate::shell::{self, Kind};
+use flate2::read::GzDecoder;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// The manual sections programs are documented in
+const SECTIONS: [&str; 3] = ["1", "8", "6"];
+
+/// What a man or tldr page says about a program.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Page {
+    /// The one line summary of what the program does.
+    pub summary: Option<String>,
+    /// The options the page describes.
+    pub options: Vec<Described>,
+}
+
+/// An option a page describes, under all the names it has, like `-a` and `--all`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Described {
+    /// The names of the option.
+    pub flags: Vec<String>,
+    /// What the option does.
+    pub text: String,
+}
+
+impl Page {
+    /// Returns what `flag` does, which may be written with its value, like `--color=auto`.
+    pub fn describe(&self, flag: &str) -> Option<&str> {
+        let find = |flag: &str| {
+            self.options
+                .iter()
+                .find(|option| option.flags.iter().any(|f| f == flag))
+                .map(|option| option.text.as_str())
+        };
+        find(flag).or_else(|| find(flag.split_once('=')?.0))
+    }
+}
+
+/// A program in a command, with what is known about it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    /// The program with its subcommand, like `git commit`.
+    pub program: String,
+    /// Whether a man or tldr page was found for it.
+    pub found: bool,
+    /// The one line summary of what the program does, from its page.
+    pub summary: Option<String>,
+    /// The flags the command passes to the program, in the order they are written.
+    pub flags: Vec<Flag>,
+}
+
+/// A flag of a command, and what it does when its page says so.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flag {
+    /// The flag as it is written in the command.
+    pub flag: String,
+    /// What the flag does, if the page says.
+    pub text: Option<String>,
+}
+
+/// Splits `command` into the programs it runs and their flags, and describes
+/// them with the pages `lookup` finds by name. The page of a subcommand is
+/// looked up as `git-commit` first, then as the page of the program itself.
+pub fn explain(command: &str, mut lookup: impl FnMut(&str) -> Option<Page>) -> Vec<Part> {
+    let mut parts: Vec<Part> = Vec::new();
+    let mut page = None;
+    for token in shell::tokens(command) {
+        let text = &command[token.range];
+        match token.kind {
+            Kind::Command => {
+                let program = Path::new(text)
+                    .file_name()
+                    .map_or(text.into(), |name| name.to_string_lossy());
+                page = lookup(&program);
+                parts.push(Part {
+                    program: program.into_owned(),
+                    found: page.is_some(),
+                    summary: page.as_ref().and_then(|page| page.summary.clone()),
+                    flags: Vec::new(),
+                });
+            }
+            Kind::Subcommand => {
+                if let Some(part) = parts.last_mut() {
+                    if let Some(subcommand) = lookup(&format!("{}-{}", part.program, text)) {
+                        part.found = true;
+                        part.summary = subcommand.summary.clone();
+                        page = Some(subcommand);
+                    }
+                    part.program = format!("{} {}", part.program, text);
+                }
+            }
+            Kind::Flag => {
+                if let Some(part) = parts.last_mut() {
+                    part.flags.extend(describe_flag(page.as_ref(), text));
+                }
+            }
+            _ => {}
+        }
+    }
+    parts
+}
+
+// Flags the page doesn't know, like `-la`, may be several short flags in one
+fn describe_flag(page: Option<&Page>, flag: &str) -> Vec<Flag> {
+    let flag = flag.trim_end_matches('=');
+    let describe = |flag: &str| Flag {
+        flag: flag.to_string(),
+        text: page
+            .and_then(|page| page.describe(flag))
+            .map(str::to_string),
+    };
+    let whole = describe(flag);
+    let short = flag
+        .strip_prefix('-')
+        .filter(|letters| letters.len() > 1 && letters.chars().all(|c| c.is_ascii_alphanumeric()));
+    match short {
+        Some(letters) if whole.text.is_none() && page.is_some() => letters
+            .chars()
+            .map(|letter| describe(&format!("-{}", letter)))
+            .collect(),
+        _ => vec![whole],
+    }
+}
+
+/// Reads the man page and the tldr page of `name`, whichever of them are
+/// installed, into one page. What the man page says comes first.
+pub fn lookup(name: &str) -> Option<Page> {
+    // Names are passed to `man` and joined to paths
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c));
+    if name.is_empty() || name.starts_with(['-', '.']) || !valid {
+        return None;
+    }
+    let man = man_page(name).map(|source| parse_roff(&source));
+    let tldr = tldr_page(name).map(|source| parse_tldr(&source));
+    match (man, tldr) {
+        (Some(mut man), Some(tldr)) => {
+            man.summary = man.summary.or(tldr.summary);
+            man.options.extend(tldr.options);
+            Some(man)
+        }
+        (man, tldr) => man.or(tldr),
+    }
+}
+
+/// Looks up what commands are made of on a thread of its own, since reading
+/// man pages can take a while, remembering what it found.
+pub struct Explainer {
+    requests: Sender<String>,
+    answers: Receiver<(String, Vec<Part>)>,
+    explained: HashMap<String, Vec<Part>>,
+    asked: HashSet<String>,
+}
+
+impl Default for Explainer {
+    fn default() -> Explainer {
+        Explainer::new(lookup)
+    }
+}
+
+impl Explainer {
+    /// Describes commands with the pages `lookup` finds, which is called
+    /// once for each name.
+    pub fn new(mut lookup: impl FnMut(&str) -> Option<Page> + Send + 'static) -> Explainer {
+        let (requests, commands) = mpsc::channel::<String>();
+        let (sender, answers) = mpsc::channel();
+        thread::spawn(move || {
+            let mut pages: HashMap<String, Option<Page>> = HashMap::new();
+            for command in commands {
+                let parts = explain(&command, |name| {
+                    pages
+                        .entry(name.to_string())
+                        .or_insert_with(|| lookup(name))
+                        .clone()
+                });
+                // Nothing is left to send it to once hui has quit
+                if sender.send((command, parts)).is_err() {
+                    break;
+                }
+            }
+        });
+        Explainer {
+            requests,
+            answers,
+            explained: HashMap::new(),
+            asked: HashSet::new(),
+        }
+    }
+
+    /// Returns what `command` is made of, or `None` while its pages are still
+    /// being read. The first call for a command starts reading them.
+    pub fn explain(&mut self, command: &str) -> Option<&[Part]> {
+        while let Ok((command, parts)) = self.answers.try_recv() {
+            self.explained.insert(command, parts);
+        }
+        if !self.explained.contains_key(command) && self.asked.insert(command.to_string()) {
+            let _ = self.requests.send(command.to_string());
+        }
+        self.explained.get(command).map(Vec::as_slice)
+    }
+}
+
+/// The directories man pages are looked for in without `man`: `$MANPATH`,
+/// where an empty entry stands for the usual directories, or else those.
+fn man_dirs() -> Vec<PathBuf> {
+    let defaults = [
+        "/usr/local/share/man",
+        "/usr/share/man",
+        "/usr/local/man",
+        "/opt/homebrew/share/man",
+    ]
+    .map(PathBuf::from);
+    match env::var_os("MANPATH") {
+        Some(manpath) if !manpath.is_empty() => env::split_paths(&manpath)
+            .flat_map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    defaults.to_vec()
+                } else {
+                    vec![dir]
+                }
+            })
+            .collect(),
+        _ => defaults.to_vec(),
+    }
+}
+
+/// Returns the roff source of the man page of `name`.
+fn man_page(name: &str) -> Option<String> {
+    // `man` knows best where its pages are, when it is installed
+    let path = process::Command::new("man")
+        .args(["-w", name])
+        .stderr(process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let found = String::from_utf8_lossy(&output.stdout);
+            Some(PathBuf::from(found.lines().next()?.trim()))
+        })
+        .filter(|path| path.is_file())
+        .or_else(|| {
+            man_dirs().into_iter().find_map(|dir| {
+                SECTIONS.iter().find_map(|section| {
+                    let file = format!("{}.{}", name, section);
+                    let path = dir.join(format!("man{}", section)).join(file);
+                    [path.with_extension(format!("{}.gz", section)), path]
+                        .into_iter()
+                        .find(|path| path.is_file())
+                })
+            })
+        })?;
+    let source = read_man_file(&path)?;
+    // A page that is another page under a different name includes it
+    match source.trim().strip_prefix(".so ") {
+        Some(other) => {
+            let root = path.parent()?.parent()?;
+            read_man_file(&root.join(other.trim()))
+                .or_else(|| read_man_file(&root.join(format!("{}.gz", other.trim()))))
+        }
+        None => Some(source),
+    }
+}
+
+fn read_man_file(path: &Path) -> Option<String> {
+    let contents = fs::read(path).ok()?;
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&contents[..])
+            .read_to_end(&mut decompressed)
+            .ok()?;
+        return Some(String::from_utf8_lossy(&decompressed).into_owned());
+    }
+    Some(String::from_utf8_lossy(&contents).into_owned())
+}
+
+/// The caches of tldr pages kept by the tldr clients: `$HUI_TLDR_DIR`,
+/// then those of tealdeer, the Node.js client and the Python client.
+fn tldr_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::var_os("HUI_TLDR_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    let cache = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".cache")));
+    if let Some(cache) = cache {
+        dirs.push(cache.join("tealdeer").join("tldr-pages"));
+        dirs.push(cache.join("tldr"));
+    }
+    if let Some(home) = env::home_dir() {
+        dirs.push(home.join(".tldr").join("cache"));
+    }
+    dirs
+}
+
+/// Returns the markdown source of the tldr page of `name`, in English.
+fn tldr_page(name: &str) -> Option<String> {
+    let file = format!("{}.md", name);
+    tldr_dirs().into_iter().find_map(|dir| {
+        ["pages", "pages.en"].iter().find_map(|pages| {
+            ["common", "linux", "osx"].iter().find_map(|platform| {
+                fs::read_to_string(dir.join(pages).join(platform).join(&file)).ok()
+            })
+        })
+    })
+}
+
+/// Reads a tldr page: its summary, and each flag used in its examples
+/// described by the first example using it.
+pub fn parse_tldr(source: &str) -> Page {
+    let mut page = Page::default();
+    let mut example = None;
+    for line in source.lines().map(str::trim) {
+        if let Some(summary) = line.strip_prefix("> ") {
+            if page.summary.is_none() && !summary.starts_with("More information") {
+                page.summary = Some(summary.trim_end_matches('.').to_string());
+            }
+        } else if let Some(text) = line.strip_prefix("- ") {
+            example = Some(text.trim_end_matches(':').to_string());
+        } else if let Some(code) = line.strip_prefix('`').and_then(|l| l.strip_suffix('`')) {
+            let Some(text) = &example else { continue };
+            for token in shell::tokens(code) {
+                let flag = code[token.range].trim_end_matches('=');
+                if token.kind == Kind::Flag && page.describe(flag).is_none() {
+                    page.options.push(Described {
+                        flags: vec![flag.to_string()],
+                        text: text.clone(),
+                    });
+                }
+            }
+        }
+    }
+    page
+}
+
+/// Reads the summary and the options of a man page from its roff source.
+pub fn parse_roff(source: &str) -> Page {
+    let mut parser = Roff::default();
+    for line in source.lines() {
+        parser.line(line);
+    }
+    parser.finish()
+}
+
+#[derive(Default)]
+struct Roff {
+    page: Page,
+    // Whether the page is written with the mdoc macros
+    mdoc: bool,
+    section: String,
+    // The NAME section of a man(7) page, like `ls \- list directory contents`
+    name: String,
+    // Whether the next line is the tag of a paragraph, which names an option
+    // when it starts with a dash
+    expect_tag: bool,
+    // The option being read, and whether the first paragraph of its text is over
+    option: Option<Described>,
+    complete: bool,
+}
+
+impl Roff {
+    fn line(&mut self, line: &str) {
+        let Some(request) = line.strip_prefix(['.', '\'']) else {
+            self.text(&unescape(line));
+            return;
+        };
+        let request = request.trim_start();
+        let (name, args) = request.split_once([' ', '\t']).unwrap_or((request, ""));
+        match name {
+            "Dd" | "Nm" => self.mdoc = true,
+            "SH" | "Sh" => {
+                self.end_option();
+                self.section = unescape(&arguments(args).join(" ")).to_uppercase();
+            }
+            "SS" | "Ss" | "El" | "Bl" => self.end_option(),
+            // A paragraph inside the text of an mdoc option
+            "Pp" => self.complete = self.option.is_some(),
+            "TP" | "PP" | "P" | "LP" => {
+                self.end_option();
+                self.expect_tag = true;
+            }
+            "IP" => {
+                self.end_option();
+                let tag = arguments(args).into_iter().next().unwrap_or_default();
+                self.start_option(&unescape(&tag));
+            }
+            "It" => {
+                self.end_option();
+                self.start_option(&mdoc_text(args));
+            }
+            "Nd" => self.page.summary = Some(mdoc_text(args)),
+            "sp" if self.option.as_ref().is_some_and(|o| !o.text.is_empty()) => {
+                self.complete = true;
+            }
+            "B" | "I" | "SM" | "SB" => self.text(&unescape(&arguments(args).join(" "))),
+            "BR" | "BI" | "IB" | "IR" | "RB" | "RI" => {
+                self.text(&unescape(&arguments(args).concat()))
+            }
+            _ if self.mdoc && is_mdoc_macro(name) => self.text(&mdoc_text(request)),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        if std::mem::take(&mut self.expect_tag) {
+            self.start_option(text);
+        } else if let Some(option) = self.option.as_mut() {
+            if !self.complete {
+                if !option.text.is_empty() {
+                    option.text.push(' ');
+                }
+                option.text.push_str(text);
+            }
+        } else if self.section == "NAME" && !self.mdoc {
+            self.name.push(' ');
+            self.name.push_str(text);
+        }
+    }
+
+    fn start_option(&mut self, tag: &str) {
+        self.expect_tag = false;
+        self.complete = false;
+        let mut flags = Vec::new();
+        for flag in tag.split([',', '|']) {
+            let flag = flag.trim();
+            // Git writes `--[no-]verify` for both `--verify` and `--no-verify`
+            if let Some(name) = flag.strip_prefix("--[no-]") {
+                let end = name.find([' ', '=', '[', '<']).unwrap_or(name.len());
+                flags.push(format!("--no-{}", &name[..end]));
+            }
+            let flag = flag.replace("[no-]", "");
+            let end = flag.find([' ', '=', '[', '<']).unwrap_or(flag.len());
+            if end > 1 && flag.starts_with('-') {
+                flags.push(flag[..end].to_string());
+            }
+        }
+        if !flags.is_empty() {
+            self.option = Some(Described {
+                flags,
+                text: String::new(),
+            });
+        }
+    }
+
+    fn end_option(&mut self) {
+        self.expect_tag = false;
+        if let Some(mut option) = self.option.take() {
+            option.text = option.text.split_whitespace().collect::<Vec<_>>().join(" ");
+            self.page.options.push(option);
+        }
+    }
+
+    fn finish(mut self) -> Page {
+        self.end_option();
+        if self.page.summary.is_none() {
+            let name = self.name.split_whitespace().collect::<Vec<_>>().join(" ");
+            self.page.summary = name
+                .split_once(" - ")
+                .map(|(_, summary)| summary.to_string());
+        }
+        self.page
+    }
+}
+
+/// Splits the arguments of a request, which may be quoted.
+fn arguments(args: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut chars = args.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            arguments.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut argument = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                argument.push(c);
+            }
+            arguments.push(argument);
+        }
+    }
+    arguments
+}
+
+// Macro names of mdoc are a capital letter followed by a small one, like `Fl`
+fn is_mdoc_macro(word: &str) -> bool {
+    let mut chars = word.chars();
+    matches!(
+        (chars.next(), chars.next(), chars.next()),
+        (Some(a), Some(b), None) if a.is_ascii_uppercase() && b.is_ascii_lowercase()
+    )
+}
+
+/// The text an mdoc line stands for, like `-color=when` for `Fl color Ns = Ns Ar when`.
+fn mdoc_text(line: &str) -> String {
+    let mut text = String::new();
+    let mut space = false;
+    let mut flag = false;
+    for word in arguments(line) {
+        match word.as_str() {
+            "Fl" => {
+                if space {
+                    text.push(' ');
+                }
+                text.push('-');
+                space = false;
+                flag = true;
+            }
+            "Ns" => space = false,
+            _ if is_mdoc_macro(&word) => {}
+            _ => {
+                let punctuation = matches!(word.as_str(), "." | "," | ";" | ":" | ")");
+                if space && !flag && !punctuation {
+                    text.push(' ');
+                }
+                text.push_str(&unescape(&word));
+                space = true;
+                flag = false;
+            }
+        }
+    }
+    text
+}
+
+/// Turns roff escapes into the characters they stand for and drops the ones
+/// that only change the font or spacing.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        // Reads the name of a special character or string, like `em` in `\(em` or `\[em]`
+        let name = |chars: &mut std::str::Chars| match chars.next() {
+            Some('(') => chars.by_ref().take(2).collect::<String>(),
+            Some('[') => chars.by_ref().take_while(|&c| c != ']').collect(),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        };
+        match chars.next() {
+            Some('f') => {
+                name(&mut chars);
+            }
+            Some('(') => {
+                let special: String = chars.by_ref().take(2).collect();
+                unescaped.push_str(character(&special));
+            }
+            Some('[') => {
+                let special: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                unescaped.push_str(character(&special));
+            }
+            Some('*') => unescaped.push_str(character(&name(&mut chars))),
+            Some('s') => {
+                let mut size = chars.clone();
+                if matches!(size.next(), Some('+' | '-')) {
+                    chars = size;
+                }
+                chars.next();
+            }
+            Some('"') => break,
+            Some('-') => unescaped.push('-'),
+            Some('e' | '\\') => unescaped.push('\\'),
+            Some(' ' | '~' | '0') => unescaped.push(' '),
+            Some('&' | '%' | '/' | ',' | '|' | '^' | 'c' | ':' | ')') | None => {}
+            Some(other) => unescaped.push(other),
+        }
+    }
+    unescaped
+}
+
+fn character(name: &str) -> &'static str {
+    match name {
+        "em" => "—",
+        "en" => "–",
+        "hy" | "mi" => "-",
+        "aq" | "oq" | "cq" => "'",
+        "dq" | "lq" | "rq" | "Lq" | "Rq" => "\"",
+        "bu" => "•",
+        "co" => "©",
+        "rs" => "\\",
+        "ti" | "ap" => "~",
+        "ha" => "^",
+        "at" => "@",
+        "Tm" | "tm" => "™",
+        "R" | "rg" => "®",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LS: &str = r#".TH LS "1" "September 2022" "GNU coreutils 9.1" "User Commands"
+.SH NAME
+ls \- list directory contents
+.SH DESCRIPTION
+.\" Add any additional description here
+.PP
+List information about the FILEs (the current directory by default).
+.TP
+\fB\-a\fR, \fB\-\-all\fR
+do not ignore entries starting with .
+.TP
+\fB\-\-color\fR[=\fI\,WHEN\/\fR]
+color the output WHEN; more info below
+.TP
+\fB\-l\fR
+use a long listing format
+.SH AUTHOR
+"#;
+
+    const GIT_COMMIT: &str = r#".SH "NAME"
+git-commit \- Record changes to the repository
+.SH "OPTIONS"
+.PP
+\-C <commit>, \-\-reuse\-message=<commit>
+.RS 4
+Take an existing commit object, and reuse the log message\&.
+.sp
+The second paragraph\&.
+.RE
+.PP
+\-m <msg>, \-\-message=<msg>
+.RS 4
+Use the given <msg> as the commit message\&.
+.RE
+.PP
+\-n, \-\-[no\-]verify
+.RS 4
+Bypass the pre\-commit and commit\-msg hooks\&.
+.RE
+"#;
+
+    const MDOC: &str = r#".Dd March 7, 2023
+.Dt RM 1
+.Sh NAME
+.Nm rm
+.Nd remove directory entries
+.Sh DESCRIPTION
+.Bl -tag -width Fl
+.It Fl f
+Attempt to remove the files without prompting for confirmation, as in
+.Fl i .
+.It Fl -color Ns = Ns Ar when
+Color the output.
+.El
+"#;
+
+    fn page(source: &str) -> Page {
+        parse_roff(source)
+    }
+
+    #[test]
+    fn reads_the_options_of_man_pages() {
+        let ls = page(LS);
+        assert_eq!(ls.summary.as_deref(), Some("list directory contents"));
+        assert_eq!(
+            ls.describe("--all"),
+            Some("do not ignore entries starting with .")
+        );
+        assert_eq!(ls.describe("-a"), ls.describe("--all"));
+        assert_eq!(
+            ls.describe("--color=auto"),
+            Some("color the output WHEN; more info below")
+        );
+        assert_eq!(ls.describe("-x"), None);
+
+        let commit = page(GIT_COMMIT);
+        assert_eq!(
+            commit.summary.as_deref(),
+            Some("Record changes to the repository")
+        );
+        assert_eq!(
+            commit.describe("-C"),
+            Some("Take an existing commit object, and reuse the log message.")
+        );
+        assert_eq!(
+            commit.describe("--message"),
+            Some("Use the given <msg> as the commit message.")
+        );
+        assert_eq!(commit.describe("--no-verify"), commit.describe("-n"));
+        assert_eq!(commit.describe("--verify"), commit.describe("-n"));
+
+        let rm = page(MDOC);
+        assert_eq!(rm.summary.as_deref(), Some("remove directory entries"));
+        assert_eq!(
+            rm.describe("-f"),
+            Some("Attempt to remove the files without prompting for confirmation, as in -i.")
+        );
+        assert_eq!(rm.describe("--color"), Some("Color the output."));
+    }
+
+    #[test]
+    fn explains_flags_with_man_and_tldr_pages() {
+        let tldr = parse_tldr(
+            "# tar\n\n> Archiving utility.\n> More information: <https://www.gnu.org/software/tar>.\n\n\
+             - [c]reate an archive from files:\n\n`tar cf {{target.tar}} {{file1 file2}}`\n\n\
+             - E[x]tract a (compressed) archive [f]ile into the current directory [v]erbosely:\n\n\
+             `tar xvf {{path/to/source.tar}} --one-top-level`\n",
+        );
+        assert_eq!(tldr.summary.as_deref(), Some("Archiving utility"));
+
+        let pages = |name: &str| match name {
+            "ls" => Some(page(LS)),
+            "git-commit" => Some(page(GIT_COMMIT)),
+            "tar" => Some(tldr.clone()),
+            _ => None,
+        };
+        let parts = explain(
+            "ls -la --color=auto | git commit -m 'fix' && tar xf a.tar --one-top-level && foo -v",
+            pages,
+        );
+        let flags = |part: &Part| -> Vec<(String, Option<String>)> {
+            part.flags
+                .iter()
+                .map(|flag| (flag.flag.clone(), flag.text.clone()))
+                .collect()
+        };
+        let described = |flag: &str, text: &str| (flag.to_string(), Some(text.to_string()));
+
+        assert_eq!(parts.len(), 4);
+        assert_eq!(
+            flags(&parts[0]),
+            [
+                described("-l", "use a long listing format"),
+                described("-a", "do not ignore entries starting with ."),
+                described("--color", "color the output WHEN; more info below"),
+            ]
+        );
+        assert_eq!(parts[1].program, "git commit");
+        assert_eq!(
+            parts[1].summary.as_deref(),
+            Some("Record changes to the repository")
+        );
+        assert_eq!(
+            flags(&parts[1]),
+            [described(
+                "-m",
+                "Use the given <msg> as the commit message."
+            )]
+        );
+        assert_eq!(
+            flags(&parts[2]),
+            [described(
+                "--one-top-level",
+                "E[x]tract a (compressed) archive [f]ile into the current directory [v]erbosely"
+            )]
+        );
+        assert!(!parts[3].found);
+        assert_eq!(flags(&parts[3]), [("-v".to_string(), None)]);
+    }
+
+    #[test]
+    fn unescapes_roff() {
+        assert_eq!(
+            unescape(r"\fB\-\-block\-size\fR=\fI\,SIZE\/\fR \(em \*(lqx\*(rq \s-1Z\s0 \e\&."),
+            r#"--block-size=SIZE — "x" Z \."#
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! `hui export`: the merged history written out as JSON, CSV or a shell's
+//! own history file.
+
+use crate::history::{self, Command, Context, Entry};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// A format the history can be exported in.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A JSON array of entries
+    Json,
+    /// One JSON entry per line
+    Ndjson,
+    /// Comma-separated values with a header row
+    Csv,
+    /// A zsh extended history file
+    Zsh,
+    /// A bash history file, with timestamps when they are known
+    Bash,
+    /// A fish history file
+    Fish,
+}
+
+/// An exported entry, with everything known about it.
+#[derive(Serialize, Deserialize)]
+pub struct Record {
+    /// The command as it was run.
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// When the command was last run, in seconds since the epoch.
+    pub timestamp: Option<u64>,
+    #[serde(flatten)]
+    /// Where it ran, how it exited and how long it took, when known.
+    pub context: Context,
+    /// Names of the sources the command was found in.
+    pub sources: Vec<String>,
+    /// How many times the command was run.
+    pub runs: usize,
+}
+
+impl Record {
+    /// `labels` are the names of the history sources.
+    pub fn new(entry: &Entry, labels: &[String]) -> Record {
+        Record {
+            command: entry.command.clone(),
+            timestamp: entry.timestamp,
+            context: entry.context.clone(),
+            sources: entry
+                .sources
+                .iter()
+                .map(|source| labels[*source].clone())
+                .collect(),
+            runs: entry.runs,
+        }
+    }
+}
+
+const CSV_HEADER: [&str; 7] = [
+    "timestamp",
+    "command",
+    "cwd",
+    "exit",
+    "duration",
+    "sources",
+    "runs",
+];
+
+/// Writes the merged history in `format`, oldest entry first like shell
+/// history files are. `labels` are the names of the history sources.
+pub fn write(
+    format: ExportFormat,
+    history: &[Entry],
+    labels: &[String],
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let records = history.iter().rev().map(|entry| Record::new(entry, labels));
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, &records.collect::<Vec<_>>())?;
+            writeln!(out)?;
+        }
+        ExportFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *out, &record)?;
+                writeln!(out)?;
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "{}", CSV_HEADER.join(","))?;
+            for record in records {
+                let optional = |value: Option<String>| value.unwrap_or_default();
+                let fields = [
+                    optional(record.timestamp.map(|t| t.to_string())),
+                    record.command,
+                    optional(record.context.cwd),
+                    optional(record.context.exit.map(|e| e.to_string())),
+                    optional(record.context.duration.map(|d| d.to_string())),
+                    record.sources.join(";"),
+                    record.runs.to_string(),
+                ];
+                let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+        ExportFormat::Zsh | ExportFormat::Bash | ExportFormat::Fish => {
+            let shell = match format {
+                ExportFormat::Zsh => history::Format::Zsh,
+                ExportFormat::Bash => history::Format::Bash,
+                _ => history::Format::Fish,
+            };
+            let commands: Vec<Command> = records
+                .map(|record| Command {
+                    command: record.command,
+                    timestamp: record.timestamp,
+                    context: record.context,
+                })
+                .collect();
+            history::write(shell, &commands, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field when it needs to be, doubling any quotes in it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Format;
+
+    fn entry(command: &str, timestamp: Option<u64>, context: Context) -> Entry {
+        Entry {
+            command: command.to_string(),
+            timestamp,
+            sources: vec![0],
+            context,
+            runs: 1,
+            ignored: false,
+        }
+    }
+
+    /// Entries most recent first, as the merged history lists them, with
+    /// commands that are awkward for every format.
+    fn history(with_timestamps: bool) -> Vec<Entry> {
+        let commands = [
+            "ls -la",
+            "echo \"quoted, with a comma\"",
+            "for i in 1 2 3\ndo\n  echo $i\ndone",
+            "printf 'a\\nb\\\\c'",
+            "echo grüße 日本語 Öl ∂",
+            "git commit -m ': 1700000000:0;not a new entry'",
+            "#123456789",
+        ];
+        commands
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, command)| {
+                let timestamp = with_timestamps.then_some(1_700_000_000 + i as u64);
+                entry(command, timestamp, Context::default())
+            })
+            .collect()
+    }
+
+    fn export(format: ExportFormat, history: &[Entry]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write(format, history, &["zsh".to_string()], &mut out).unwrap();
+        out
+    }
+
+    /// Exports `history` as a shell history file and checks that reading it
+    /// back gives the same commands and timestamps, oldest first.
+    fn assert_round_trip(format: ExportFormat, shell: Format, history: &[Entry]) {
+        let commands = history::parse(shell, export(format, history)).unwrap();
+        let read: Vec<(&str, Option<u64>)> = commands
+            .iter()
+            .map(|c| (c.command.as_str(), c.timestamp))
+            .collect();
+        let expected: Vec<(&str, Option<u64>)> = history
+            .iter()
+            .rev()
+            .map(|e| (e.command.as_str(), e.timestamp))
+            .collect();
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn zsh_round_trip() {
+        assert_round_trip(ExportFormat::Zsh, Format::Zsh, &history(true));
+        assert_round_trip(ExportFormat::Zsh, Format::Zsh, &history(false));
+    }
+
+    #[test]
+    fn zsh_export_is_metafied() {
+        let exported = export(
+            ExportFormat::Zsh,
+            &[entry("echo Öl", None, Context::default())],
+        );
+        // Ö is 0xc3 0x96, and 0x96 is stored as Meta followed by 0x96 ^ 32
+        assert_eq!(exported, b"echo \xc3\x83\xb6l\n");
+    }
+
+    #[test]
+    fn bash_round_trip() {
+        // Like bash itself, a command that looks like a timestamp is read as one
+        let bash = |with_timestamps| -> Vec<Entry> {
+            history(with_timestamps)
+                .into_iter()
+                .filter(|e| !e.command.starts_with('#'))
+                .collect()
+        };
+        assert_round_trip(ExportFormat::Bash, Format::Bash, &bash(true));
+        // and multiline commands can only be told apart with timestamps
+        let single_lines: Vec<Entry> = bash(false)
+            .into_iter()
+            .filter(|e| !e.command.contains('\n'))
+            .collect();
+        assert_round_trip(ExportFormat::Bash, Format::Bash, &single_lines);
+    }
+
+    #[test]
+    fn fish_round_trip() {
+        assert_round_trip(ExportFormat::Fish, Format::Fish, &history(true));
+        assert_round_trip(ExportFormat::Fish, Format::Fish, &history(false));
+    }
+
+    #[test]
+    fn json_round_trip_keeps_metadata() {
+        let context = Context {
+            cwd: Some("/home/me/src".to_string()),
+            exit: Some(1),
+            duration: Some(2_500),
+        };
+        let history = vec![entry("cargo test", Some(1_700_000_000), context)];
+        for format in [ExportFormat::Json, ExportFormat::Ndjson] {
+            let exported = String::from_utf8(export(format, &history)).unwrap();
+            let records: Vec<Record> = match format {
+                ExportFormat::Json => serde_json::from_str(&exported).unwrap(),
+                _ => exported
+                    .lines()
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect(),
+            };
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].command, "cargo test");
+            assert_eq!(records[0].timestamp, Some(1_700_000_000));
+            assert_eq!(records[0].context.cwd.as_deref(), Some("/home/me/src"));
+            assert_eq!(records[0].context.exit, Some(1));
+            assert_eq!(records[0].context.duration, Some(2_500));
+            assert_eq!(records[0].sources, ["zsh"]);
+        }
+
+        // The JSON export can be read back as an imported history
+        let exported = export(ExportFormat::Json, &history);
+        let commands = history::parse(Format::Hui, exported).unwrap();
+        assert_eq!(commands[0].command, "cargo test");
+        assert_eq!(commands[0].context.exit, Some(1));
+    }
+
+    /// Splits CSV into records of fields, following RFC 4180.
+    fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = csv.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => record.push(std::mem::take(&mut field)),
+                '\n' if !quoted => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                c => field.push(c),
+            }
+        }
+        records
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let history = history(true);
+        let exported = String::from_utf8(export(ExportFormat::Csv, &history)).unwrap();
+        let records = parse_csv(&exported);
+        assert_eq!(records[0], CSV_HEADER);
+        let commands: Vec<&str> = records[1..].iter().map(|r| r[1].as_str()).collect();
+        let expected: Vec<&str> = history.iter().rev().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, expected);
+        assert!(records[1..].iter().all(|r| r.len() == CSV_HEADER.len()));
+        assert_eq!(records[1][0], "1700000000");
+        assert_eq!(records[1][5], "zsh");
+        assert_eq!(records[1][6], "1");
+    }
+}
@@ -0,0 +1,99 @@
+//! Starred commands, with the notes and tags added to them.
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the data file favorites are saved in.
+pub const FILE: &str = "favorites.json";
+
+/// A starred command, with an optional note and tags. Favorites are kept in
+/// hui's own data file, so they outlive the entry in the shell history.
+#[derive(Serialize, Deserialize)]
+pub struct Favorite {
+    /// The starred command.
+    pub command: String,
+    #[serde(default)]
+    /// What the command is for, in the user's words.
+    pub note: String,
+    #[serde(default)]
+    /// Tags without their leading `#`, searched as `#tag`.
+    pub tags: Vec<String>,
+}
+
+impl Favorite {
+    /// A favorite for `command`, without a note or tags.
+    pub fn new(command: &str) -> Favorite {
+        Favorite {
+            command: command.to_string(),
+            note: String::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// The note and tags, as shown after the command, e.g. `restarts the api #k8s`.
+    pub fn annotation(&self) -> String {
+        let tags = self.tags.iter().map(|tag| format!("#{}", tag));
+        let mut parts: Vec<String> = tags.collect();
+        if !self.note.is_empty() {
+            parts.insert(0, self.note.clone());
+        }
+        parts.join(" ")
+    }
+
+    /// The text searched for this favorite: its command, note and tags.
+    pub fn searchable(&self) -> String {
+        format!("{} {}", self.command, self.annotation())
+    }
+}
+
+/// Editing the note and tags of a favorite.
+pub struct NoteForm {
+    /// The command the note and tags are for.
+    pub command: String,
+    /// The note as typed so far.
+    pub note: String,
+    /// Tags separated by spaces or commas, with or without a leading `#`.
+    pub tags: String,
+    /// 0 while editing the note, 1 while editing the tags.
+    pub field: usize,
+}
+
+impl NoteForm {
+    /// A form for the note and tags of `command`, filled in with those of
+    /// `favorite` when it is already starred.
+    pub fn new(favorite: Option<&Favorite>, command: &str) -> NoteForm {
+        NoteForm {
+            command: command.to_string(),
+            note: favorite.map_or(String::new(), |f| f.note.clone()),
+            tags: favorite.map_or(String::new(), |f| f.tags.join(" ")),
+            field: 0,
+        }
+    }
+
+    /// The field being edited.
+    pub fn value_mut(&mut self) -> &mut String {
+        match self.field {
+            0 => &mut self.note,
+            _ => &mut self.tags,
+        }
+    }
+
+    /// Switches between editing the note and the tags.
+    pub fn switch_field(&mut self) {
+        self.field = 1 - self.field;
+    }
+
+    /// The favorite with the note and tags as typed, the tags split into words.
+    pub fn favorite(&self) -> Favorite {
+        Favorite {
+            command: self.command.clone(),
+            note: self.note.trim().to_string(),
+            tags: self
+                .tags
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .map(|tag| tag.trim_start_matches('#'))
+                .filter(|tag| !tag.is_empty())
+                .map(|tag| tag.to_string())
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,782 @@
+//! Reading shell history files, and merging them into one timeline.
+
+// This uses a lot of what hstr-rs did to parse ZSH history:
+// https://github.com/overclockworked64/hstr-rs/blob/master/src/hstr.rs
+use crate::ignore::Ignore;
+use crate::store;
+use crate::zsh;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{hash_map, HashMap};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// A shell history file format.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// zsh's history file, with or without extended history.
+    Zsh,
+    /// bash's history file, with or without `HISTTIMEFORMAT` timestamps.
+    Bash,
+    /// fish's history file.
+    Fish,
+    /// Commands imported from another tool by `hui import`.
+    Hui,
+}
+
+impl Format {
+    fn parse(name: &str) -> Option<Format> {
+        match name {
+            "zsh" => Some(Format::Zsh),
+            "bash" => Some(Format::Bash),
+            "fish" => Some(Format::Fish),
+            "hui" => Some(Format::Hui),
+            _ => None,
+        }
+    }
+
+    /// The name of the format, as written in `$HUI_SOURCES`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Zsh => "zsh",
+            Format::Bash => "bash",
+            Format::Fish => "fish",
+            Format::Hui => "hui",
+        }
+    }
+
+    /// Where the shell keeps its history by default.
+    fn default_file(self) -> PathBuf {
+        match self {
+            Format::Zsh => PathBuf::from("~/.zsh_history"),
+            Format::Bash => PathBuf::from("~/.bash_history"),
+            Format::Fish => match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+                Some(dir) => PathBuf::from(dir).join("fish").join("fish_history"),
+                None => PathBuf::from("~/.local/share/fish/fish_history"),
+            },
+            Format::Hui => PathBuf::new(),
+        }
+    }
+}
+
+/// Somewhere commands are read from. `Source` reads shell history files and
+/// what was imported from other tools; anything else can be searched along
+/// with them by implementing this trait.
+///
+/// Only `label`, `name` and `read` are required. The other methods let the
+/// interface fill in while a large source is still being read and show what
+/// is added to it while it is open.
+pub trait HistorySource: Send + Sync {
+    /// Short name shown next to the entries that came from this source.
+    fn label(&self) -> &str;
+
+    /// Longer name shown in the status bar, such as the path of the file.
+    fn name(&self) -> &str;
+
+    /// Reads the commands in this source, oldest first.
+    fn read(&self) -> io::Result<Vec<Command>>;
+
+    /// The file this source is read from, which is watched for changes. A
+    /// source without one is read once.
+    fn path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Reads the first `len` bytes of the file in chunks from its end,
+    /// handing the commands of each chunk to `chunk` as soon as it is parsed,
+    /// so the most recent commands come first. By default the whole source is
+    /// read as a single chunk.
+    fn read_backwards(&self, len: u64, chunk: &mut dyn FnMut(Vec<Command>)) -> io::Result<()> {
+        let _ = len;
+        chunk(self.read()?);
+        Ok(())
+    }
+
+    /// Reads the commands appended to the file since it was `len` bytes long,
+    /// up to the last complete line, and returns them with the length read up
+    /// to. Returns `None` when the whole source has to be read again instead,
+    /// which it does by default.
+    fn read_appended(&self, len: u64) -> io::Result<Option<(Vec<Command>, u64)>> {
+        let _ = len;
+        Ok(None)
+    }
+}
+
+/// A history file to load.
+#[derive(Clone)]
+pub struct Source {
+    /// Short name shown next to the entries that came from this source.
+    pub label: String,
+    /// The path as given, shown in the status bar.
+    pub name: String,
+    path: PathBuf,
+    // `None` when the format is guessed from the file itself
+    format: Option<Format>,
+}
+
+impl Source {
+    /// Parses a source as written in `$HUI_SOURCES`: a shell name (`zsh`,
+    /// `bash` or `fish`) for that shell's own history file, or a path to any
+    /// history file, optionally prefixed by its format, e.g.
+    /// `zsh:~/backup/.zsh_history`.
+    pub fn parse(spec: &str) -> Source {
+        let spec = spec.trim();
+        if let Some(format) = Format::parse(spec).filter(|format| *format != Format::Hui) {
+            let path = format.default_file();
+            return Source {
+                label: format.name().to_string(),
+                name: path.display().to_string(),
+                path,
+                format: Some(format),
+            };
+        }
+
+        let (format, path) = match spec.split_once(':') {
+            Some((format, path)) if Format::parse(format).is_some() => {
+                (Format::parse(format), path)
+            }
+            _ => (None, spec),
+        };
+        let path = PathBuf::from(path);
+        let label = path
+            .file_name()
+            .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+            .unwrap_or_else(|| spec.to_string());
+        Source {
+            label,
+            name: path.display().to_string(),
+            path,
+            format,
+        }
+    }
+
+    /// A source reading the file at `path` in `format`, listed as `label` and
+    /// shown in the status bar as `name`.
+    pub fn new(label: &str, name: &str, path: PathBuf, format: Format) -> Source {
+        Source {
+            label: label.to_string(),
+            name: name.to_string(),
+            path,
+            format: Some(format),
+        }
+    }
+
+    /// Opens the file, guessing its format from its start when it isn't known.
+    fn open(&self) -> io::Result<(fs::File, Format)> {
+        let mut file = fs::File::open(self.full_path()?)?;
+        let format = match self.format {
+            Some(format) => format,
+            None => {
+                let mut start = [0; 8];
+                let read = file.read(&mut start)?;
+                file.rewind()?;
+                self.guess_format(&start[..read])
+            }
+        };
+        Ok((file, format))
+    }
+
+    /// The path with a leading `~` expanded to the home directory.
+    pub fn full_path(&self) -> io::Result<PathBuf> {
+        match self.path.strip_prefix("~") {
+            Ok(rest) => match env::home_dir() {
+                Some(home) => Ok(home.join(rest)),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "couldn't get the home directory",
+                )),
+            },
+            Err(_) => Ok(self.path.clone()),
+        }
+    }
+
+    /// Guesses the format of a history file from its name, then its contents.
+    fn guess_format(&self, contents: &[u8]) -> Format {
+        let name = self.label.to_lowercase();
+        for format in [Format::Zsh, Format::Fish, Format::Bash] {
+            if name.contains(format.name()) {
+                return format;
+            }
+        }
+        if contents.starts_with(b": ") {
+            Format::Zsh
+        } else if contents.starts_with(b"- cmd: ") {
+            Format::Fish
+        } else {
+            Format::Bash
+        }
+    }
+}
+
+impl HistorySource for Source {
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> Option<PathBuf> {
+        self.full_path().ok()
+    }
+
+    fn read(&self) -> io::Result<Vec<Command>> {
+        let (file, format) = self.open()?;
+        if format == Format::Hui {
+            return parse(format, store::read(&self.full_path()?)?);
+        }
+        read(format, BufReader::new(file))
+    }
+
+    // Every chunk is twice as large as the one after it, so that however
+    // many there are, joining them takes linear time.
+    fn read_backwards(&self, len: u64, chunk: &mut dyn FnMut(Vec<Command>)) -> io::Result<()> {
+        let (mut file, format) = self.open()?;
+        // What hui keeps itself may be encrypted, and is read in one go
+        if format == Format::Hui {
+            chunk(parse(format, store::read(&self.full_path()?)?)?);
+            return Ok(());
+        }
+        // Whether bash wrote timestamps is told by the first line of the file only
+        let timestamped = match format {
+            Format::Bash => {
+                let mut first = Vec::new();
+                BufReader::new(&file).read_until(b'\n', &mut first)?;
+                Some(is_bash_timestamp(trim_line(&first)))
+            }
+            _ => None,
+        };
+
+        let mut end = len;
+        let mut size = FIRST_CHUNK;
+        while end > 0 {
+            let start = match end.checked_sub(size) {
+                Some(from) if from > 0 => entry_start(&file, format, timestamped, from, end)?,
+                _ => 0,
+            };
+            size *= 2;
+            // No entry starts in that much of the file, so take more of it
+            if start == end {
+                continue;
+            }
+            file.seek(SeekFrom::Start(start))?;
+            let reader = BufReader::new((&file).take(end - start));
+            chunk(Parser::new(format, timestamped).read(reader)?);
+            end = start;
+        }
+        Ok(())
+    }
+
+    fn read_appended(&self, len: u64) -> io::Result<Option<(Vec<Command>, u64)>> {
+        let (mut file, format) = self.open()?;
+        if matches!(format, Format::Fish | Format::Hui) || file.metadata()?.len() < len {
+            return Ok(None);
+        }
+
+        file.seek(SeekFrom::Start(len))?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+        // A line still being written is read next time
+        let complete = appended
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        appended.truncate(complete);
+        let commands = parse(format, appended)?;
+        Ok(Some((commands, len + complete as u64)))
+    }
+}
+
+/// A command as read from a history file.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Command {
+    /// The command as it was typed.
+    pub command: String,
+    /// When the command was run, in seconds since the epoch, if the file records it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    /// Where it ran, how it exited and how long it took, when known.
+    #[serde(flatten)]
+    pub context: Context,
+}
+
+/// What else is known about a run of a command. Shell history files don't
+/// record any of this, but tools like atuin do.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Context {
+    /// The directory the command was run in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// The exit status of the command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit: Option<i32>,
+    /// How long the command took, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+}
+
+impl Context {
+    /// Whether nothing is known about the run.
+    pub fn is_empty(&self) -> bool {
+        self.cwd.is_none() && self.exit.is_none() && self.duration.is_none()
+    }
+
+    /// Describes the run, e.g. `in ~/src/hui, exit 1, took 2.3 s`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(cwd) = &self.cwd {
+            parts.push(format!("in {}", cwd));
+        }
+        if let Some(exit) = self.exit {
+            parts.push(format!("exit {}", exit));
+        }
+        if let Some(duration) = self.duration {
+            parts.push(if duration < 1000 {
+                format!("took {} ms", duration)
+            } else {
+                format!("took {:.1} s", duration as f64 / 1000.0)
+            });
+        }
+        parts.join(", ")
+    }
+}
+
+impl Command {
+    /// A command without any context.
+    pub fn new(command: String, timestamp: Option<u64>) -> Command {
+        Command {
+            command,
+            timestamp,
+            context: Context::default(),
+        }
+    }
+}
+
+/// A command in the merged history.
+pub struct Entry {
+    /// The command as it was typed.
+    pub command: String,
+    /// When the run the entry is listed with happened, in seconds since the epoch.
+    pub timestamp: Option<u64>,
+    /// Indexes of the sources the command was found in.
+    pub sources: Vec<usize>,
+    /// The context of the latest run of the command that has one.
+    pub context: Context,
+    /// How many times the command was run, counting the duplicates left out.
+    pub runs: usize,
+    /// Whether the ignore rules hide the command.
+    pub ignored: bool,
+}
+
+/// Which of the runs of a command are kept when merging the history.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dedup {
+    /// Keep the most recent run of each command (`latest`, the default).
+    Latest,
+    /// Keep the first run of each command, where it was first used (`oldest`).
+    Oldest,
+    /// Keep every run, each with its own timestamp (`none`).
+    Off,
+    /// Like `Latest`, but commands that only differ in spacing are the same
+    /// command (`whitespace`). The most recent spelling is kept.
+    Whitespace,
+}
+
+impl Dedup {
+    /// Parses the name of a strategy, as written in `$HUI_DEDUP`.
+    pub fn parse(name: &str) -> Option<Dedup> {
+        match name {
+            "latest" => Some(Dedup::Latest),
+            "oldest" => Some(Dedup::Oldest),
+            "none" => Some(Dedup::Off),
+            "whitespace" => Some(Dedup::Whitespace),
+            _ => None,
+        }
+    }
+}
+
+/// Merges the commands of several sources into one timeline, most recent first.
+///
+/// Commands are ordered by their timestamp. A command without one is placed
+/// right after the command before it in the same file, so every file keeps
+/// its own order and files without any timestamps come first. Repeated
+/// commands are then removed as `dedup` says, and the commands `ignore`
+/// matches are marked as ignored.
+pub fn merge(histories: &[Vec<Command>], dedup: Dedup, ignore: &Ignore) -> Vec<Entry> {
+    let mut timeline = Vec::new();
+    for (source, commands) in histories.iter().enumerate() {
+        let mut time = 0;
+        for command in commands {
+            time = command.timestamp.unwrap_or(time);
+            timeline.push((time, source, command));
+        }
+    }
+    // The sort is stable, so commands run at the same time stay in source order
+    timeline.sort_by_key(|(time, _, _)| *time);
+
+    let timeline = timeline
+        .into_iter()
+        .map(|(_, source, command)| (source, command));
+    let entry = |(source, command): (usize, &Command)| Entry {
+        command: command.command.clone(),
+        timestamp: command.timestamp,
+        sources: vec![source],
+        context: command.context.clone(),
+        runs: 1,
+        ignored: ignore.is_ignored(&command.command),
+    };
+    match dedup {
+        Dedup::Latest => remove_duplicates(timeline.rev(), true, Cow::Borrowed, entry),
+        Dedup::Oldest => reverse(remove_duplicates(timeline, false, Cow::Borrowed, entry)),
+        Dedup::Off => timeline.rev().map(entry).collect(),
+        Dedup::Whitespace => remove_duplicates(
+            timeline.rev(),
+            true,
+            |command| Cow::Owned(command.split_whitespace().collect::<Vec<_>>().join(" ")),
+            entry,
+        ),
+    }
+}
+
+/// Reads a history file in `format`, oldest command first, in a single pass.
+pub fn read(format: Format, reader: impl BufRead) -> io::Result<Vec<Command>> {
+    match format {
+        Format::Hui => serde_json::from_reader(reader)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        _ => Parser::new(format, None).read(reader),
+    }
+}
+
+/// Parses the contents of a history file in `format`, oldest command first.
+pub fn parse(format: Format, contents: Vec<u8>) -> io::Result<Vec<Command>> {
+    read(format, &contents[..])
+}
+
+/// Writes `commands` as a history file in `format`, the way the shell itself
+/// would, so that `parse` reads them back unchanged.
+pub fn write(format: Format, commands: &[Command], out: &mut dyn Write) -> io::Result<()> {
+    match format {
+        Format::Zsh => {
+            for command in commands {
+                out.write_all(&zsh::encode(command))?;
+            }
+        }
+        Format::Bash => write_bash(commands, out)?,
+        Format::Fish => {
+            for command in commands {
+                writeln!(out, "- cmd: {}", escape_fish(&command.command))?;
+                if let Some(timestamp) = command.timestamp {
+                    writeln!(out, "  when: {}", timestamp)?;
+                }
+            }
+        }
+        Format::Hui => {
+            serde_json::to_writer_pretty(&mut *out, commands)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+// The size of the most recent chunk `Source::read_backwards` reads
+const FIRST_CHUNK: u64 = 64 * 1024;
+
+/// Turns the lines of a shell history file into commands, one line at a
+/// time, so a file is parsed in a single pass over it.
+struct Parser {
+    format: Format,
+    // Whether bash wrote a timestamp before every command, which the first line tells
+    timestamped: Option<bool>,
+    zsh: zsh::Decoder,
+    // The timestamp of the next bash command, and whether it was the last line read
+    time: Option<u64>,
+    after_timestamp: bool,
+    commands: Vec<Command>,
+}
+
+impl Parser {
+    fn new(format: Format, timestamped: Option<bool>) -> Parser {
+        Parser {
+            format,
+            timestamped,
+            zsh: zsh::Decoder::new(),
+            time: None,
+            after_timestamp: false,
+            commands: Vec::new(),
+        }
+    }
+
+    fn read(mut self, mut reader: impl BufRead) -> io::Result<Vec<Command>> {
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            match self.format {
+                Format::Zsh => self.commands.extend(self.zsh.line(&line)),
+                Format::Bash => self.bash_line(&String::from_utf8_lossy(trim_line(&line))),
+                Format::Fish => self.fish_line(&String::from_utf8_lossy(trim_line(&line))),
+                Format::Hui => unreachable!("imported commands are stored as JSON"),
+            }
+            line.clear();
+        }
+        self.commands.extend(self.zsh.finish());
+        Ok(remove_empty(self.commands))
+    }
+
+    fn bash_line(&mut self, line: &str) {
+        /* With HISTTIMEFORMAT set, bash writes a "#1330648651" line before each
+         * command. All the lines up to the next one then belong to that command,
+         * which is how multiline commands are kept.
+         */
+        let timestamped = *self
+            .timestamped
+            .get_or_insert_with(|| is_bash_timestamp(line.as_bytes()));
+        if is_bash_timestamp(line.as_bytes()) {
+            self.time = line[1..].parse().ok();
+            self.after_timestamp = true;
+            return;
+        }
+        match self
+            .commands
+            .last_mut()
+            .filter(|_| timestamped && !self.after_timestamp)
+        {
+            Some(command) => {
+                command.command.push('\n');
+                command.command.push_str(line);
+            }
+            None => self
+                .commands
+                .push(Command::new(line.to_string(), self.time.take())),
+        }
+        self.after_timestamp = false;
+    }
+
+    fn fish_line(&mut self, line: &str) {
+        /* The fish history file is a list of entries like:
+         *
+         * - cmd: sudo reboot
+         *   when: 1330648651
+         *   paths:
+         *     - ...
+         *
+         * with backslashes and newlines in the command escaped.
+         */
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            self.commands
+                .push(Command::new(unescape_fish(command), None));
+        } else if let Some(when) = line.strip_prefix("  when: ") {
+            if let Some(command) = self.commands.last_mut() {
+                command.timestamp = when.trim().parse().ok();
+            }
+        }
+    }
+}
+
+// A "#1330648651" line bash writes before a command when HISTTIMEFORMAT is set
+fn is_bash_timestamp(line: &[u8]) -> bool {
+    line.len() >= 10 && line[0] == b'#' && line[1..].iter().all(u8::is_ascii_digit)
+}
+
+/// A line without the line break at its end, which may be a Windows one.
+fn trim_line(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Finds where the first entry that starts after `from` in a history file
+/// in `format` is, or returns `to` when none starts before it.
+fn entry_start(
+    file: &fs::File,
+    format: Format,
+    timestamped: Option<bool>,
+    from: u64,
+    to: u64,
+) -> io::Result<u64> {
+    let mut file = file;
+    file.seek(SeekFrom::Start(from))?;
+    let mut reader = BufReader::new(file);
+    // Skip the rest of the line `from` is in, since it may have started before
+    let mut line = Vec::new();
+    let mut position = from + reader.read_until(b'\n', &mut line)? as u64;
+    let mut continued = zsh::continues(trim_line(&line));
+    while position < to {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        let starts = match format {
+            Format::Zsh => !continued,
+            Format::Bash => timestamped != Some(true) || is_bash_timestamp(trim_line(&line)),
+            Format::Fish => line.starts_with(b"- cmd: "),
+            Format::Hui => false,
+        };
+        if starts {
+            return Ok(position);
+        }
+        continued = zsh::continues(trim_line(&line));
+        position += read as u64;
+    }
+    Ok(to)
+}
+
+fn write_bash(commands: &[Command], out: &mut dyn Write) -> io::Result<()> {
+    // Once one command has a timestamp every command needs one, or bash would
+    // read it as part of the command before it. Those without one get the
+    // timestamp of the command before them.
+    if commands.iter().all(|command| command.timestamp.is_none()) {
+        for command in commands {
+            writeln!(out, "{}", command.command)?;
+        }
+        return Ok(());
+    }
+    let mut time = 0;
+    for command in commands {
+        time = command.timestamp.unwrap_or(time);
+        writeln!(out, "#{}", time)?;
+        writeln!(out, "{}", command.command)?;
+    }
+    Ok(())
+}
+
+fn unescape_fish(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn escape_fish(command: &str) -> String {
+    command.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn remove_empty(mut commands: Vec<Command>) -> Vec<Command> {
+    commands.retain(|command| !command.command.is_empty());
+    commands
+}
+
+fn reverse(mut history: Vec<Entry>) -> Vec<Entry> {
+    history.reverse();
+    history
+}
+
+/// Keeps the first of the commands that are the same by `key`, counting its
+/// runs and noting every source it was found in. Only the commands kept are
+/// made into entries, so that a long history isn't copied whole. The history
+/// is most recent first when `newest_first` is set, and oldest first otherwise.
+fn remove_duplicates<'a>(
+    history: impl Iterator<Item = (usize, &'a Command)>,
+    newest_first: bool,
+    key: impl Fn(&'a str) -> Cow<'a, str>,
+    entry: impl Fn((usize, &'a Command)) -> Entry,
+) -> Vec<Entry> {
+    let mut unique: Vec<Entry> = Vec::new();
+    let mut seen: HashMap<Cow<str>, usize> = HashMap::new();
+    for (source, command) in history {
+        match seen.entry(key(&command.command)) {
+            hash_map::Entry::Occupied(index) => {
+                let kept = &mut unique[*index.get()];
+                kept.runs += 1;
+                // Keep the context of the latest run that has one
+                if !command.context.is_empty() && (!newest_first || kept.context.is_empty()) {
+                    kept.context = command.context.clone();
+                }
+                if !kept.sources.contains(&source) {
+                    kept.sources.push(source);
+                }
+            }
+            hash_map::Entry::Vacant(vacant) => {
+                vacant.insert(unique.len());
+                unique.push(entry((source, command)));
+            }
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Enough commands for several chunks, some spanning several lines.
+    fn commands(timestamps: bool) -> Vec<Command> {
+        (0..20_000)
+            .map(|i| {
+                let mut command = format!("echo {} grüße", i);
+                if i % 7 == 0 {
+                    command.push_str("\n  and more");
+                }
+                Command::new(command, timestamps.then_some(1_700_000_000 + i))
+            })
+            .collect()
+    }
+
+    /// Writes `commands` to a file in `format` and reads it back both forwards
+    /// and backwards in chunks.
+    fn read_both_ways(format: Format, commands: &[Command]) -> (Vec<String>, Vec<String>) {
+        let path = env::temp_dir().join(format!("hui-test-{}-{}", format.name(), commands.len()));
+        let mut contents = Vec::new();
+        write(format, commands, &mut contents).unwrap();
+        fs::write(&path, &contents).unwrap();
+        let source = Source::new("test", "test", path.clone(), format);
+
+        let forwards = source.read().unwrap();
+        let mut backwards: Vec<Command> = Vec::new();
+        let mut chunks = 0;
+        source
+            .read_backwards(contents.len() as u64, &mut |mut older| {
+                older.append(&mut backwards);
+                backwards = older;
+                chunks += 1;
+            })
+            .unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(chunks > 1);
+
+        let lines = |commands: Vec<Command>| commands.into_iter().map(|c| c.command).collect();
+        (lines(forwards), lines(backwards))
+    }
+
+    #[test]
+    fn reading_backwards_reads_the_same_commands() {
+        for (format, timestamps) in [
+            (Format::Zsh, true),
+            (Format::Zsh, false),
+            (Format::Bash, true),
+            (Format::Fish, true),
+        ] {
+            let commands = commands(timestamps);
+            let (forwards, backwards) = read_both_ways(format, &commands);
+            let expected: Vec<&str> = commands.iter().map(|c| c.command.as_str()).collect();
+            assert_eq!(forwards, expected);
+            assert_eq!(backwards, expected);
+        }
+
+        // Without timestamps, bash has no multiline commands
+        let commands: Vec<Command> = commands(false)
+            .into_iter()
+            .filter(|c| !c.command.contains('\n'))
+            .collect();
+        let (forwards, backwards) = read_both_ways(Format::Bash, &commands);
+        assert_eq!(forwards, backwards);
+    }
+}
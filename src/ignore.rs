@@ -0,0 +1,79 @@
+//! Ignore rules, which hide noisy commands from the list.
+
+use crate::store;
+use regex::RegexSet;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io;
+
+/// Name of the data file the ignore rules are read from.
+pub const FILE: &str = "ignore.json";
+
+/// Rules for commands that are hidden from the list, as written in the data file:
+///
+/// ```json
+/// {
+///   "exact": ["ls", "cd ..", "clear"],
+///   "regex": ["^git (status|diff)$"],
+///   "shorter_than": 3,
+///   "leading_space": true
+/// }
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Rules {
+    /// Commands ignored as they are written, apart from surrounding spaces.
+    pub exact: Vec<String>,
+    /// Commands matching any of these regexes are ignored.
+    pub regex: Vec<String>,
+    /// Commands shorter than this many characters are ignored.
+    pub shorter_than: usize,
+    /// Commands starting with a space are ignored, like bash's `HISTCONTROL=ignorespace`.
+    pub leading_space: bool,
+}
+
+/// The compiled ignore rules. The default ignores nothing.
+#[derive(Default)]
+pub struct Ignore {
+    exact: HashSet<String>,
+    regex: Option<RegexSet>,
+    shorter_than: usize,
+    leading_space: bool,
+}
+
+impl Ignore {
+    /// Compiles the ignore rules written as `rules`.
+    pub fn new(rules: Rules) -> Result<Ignore, regex::Error> {
+        let regex = if rules.regex.is_empty() {
+            None
+        } else {
+            Some(RegexSet::new(&rules.regex)?)
+        };
+        Ok(Ignore {
+            exact: rules
+                .exact
+                .iter()
+                .map(|command| command.trim().to_string())
+                .collect(),
+            regex,
+            shorter_than: rules.shorter_than,
+            leading_space: rules.leading_space,
+        })
+    }
+
+    /// Reads the rules from the data file. Without one, nothing is ignored.
+    pub fn load() -> io::Result<Ignore> {
+        store::load_rules(FILE, |rules: &Rules| &rules.regex, Ignore::new)
+    }
+
+    /// Whether any rule hides `command`.
+    pub fn is_ignored(&self, command: &str) -> bool {
+        (self.leading_space && command.starts_with(' '))
+            || command.trim().chars().count() < self.shorter_than
+            || self.exact.contains(command.trim())
+            || self
+                .regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(command))
+    }
+}
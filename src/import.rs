@@ -0,0 +1,280 @@
+//! `hui import`: the histories of atuin, mcfly and resh, saved to be
+//! searched along with the shell histories.
+
+use crate::history::{Command, Context, Format, Source};
+use crate::store;
+use clap::ValueEnum;
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Another shell history tool whose history can be imported.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tool {
+    /// Atuin's SQLite database
+    Atuin,
+    /// McFly's SQLite database
+    Mcfly,
+    /// RESH's JSON lines history
+    Resh,
+}
+
+impl Tool {
+    /// Every tool, in the order their imports are listed.
+    pub const ALL: [Tool; 3] = [Tool::Atuin, Tool::Mcfly, Tool::Resh];
+
+    /// The tool's name, as given to `hui import`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Atuin => "atuin",
+            Tool::Mcfly => "mcfly",
+            Tool::Resh => "resh",
+        }
+    }
+
+    /// Name of the data file the commands imported from this tool are saved in.
+    pub fn file(self) -> String {
+        format!("import-{}.json", self.name())
+    }
+
+    /// Where the tool keeps its history by default. The first of its known
+    /// locations that exists is used.
+    pub fn default_path(self) -> io::Result<PathBuf> {
+        let home = env::home_dir().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "couldn't get the home directory")
+        })?;
+        let data = env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map_or_else(|| home.join(".local").join("share"), PathBuf::from);
+        let candidates = match self {
+            Tool::Atuin => vec![data.join("atuin").join("history.db")],
+            Tool::Mcfly => vec![
+                data.join("mcfly").join("history.db"),
+                home.join(".mcfly").join("history.db"),
+                home.join("Library/Application Support/McFly/history.db"),
+            ],
+            Tool::Resh => vec![
+                data.join("resh").join("history.reshjson"),
+                home.join(".resh_history.json"),
+            ],
+        };
+        let first = candidates[0].clone();
+        Ok(candidates
+            .into_iter()
+            .find(|path| path.exists())
+            .unwrap_or(first))
+    }
+
+    /// Reads the history of this tool at `path`, oldest first.
+    pub fn read(self, path: &Path) -> io::Result<Vec<Command>> {
+        if !path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: no such file", path.display()),
+            ));
+        }
+        match self {
+            Tool::Atuin => read_atuin(path).map_err(io::Error::other),
+            Tool::Mcfly => read_mcfly(path).map_err(io::Error::other),
+            Tool::Resh => read_resh(path),
+        }
+    }
+}
+
+/// The sources for everything imported so far, to be loaded along with the shell histories.
+pub fn sources() -> Vec<Source> {
+    let Ok(dir) = store::data_dir() else {
+        return Vec::new();
+    };
+    Tool::ALL
+        .iter()
+        .map(|tool| (tool, dir.join(tool.file())))
+        .filter(|(_, path)| path.exists())
+        .map(|(tool, path)| {
+            let name = format!("imported from {}", tool.name());
+            Source::new(tool.name(), &name, path, Format::Hui)
+        })
+        .collect()
+}
+
+fn open(path: &Path) -> rusqlite::Result<Connection> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+}
+
+fn read_atuin(path: &Path) -> rusqlite::Result<Vec<Command>> {
+    /* atuin keeps one row per run in its `history` table, with the time
+     * and duration in nanoseconds, and -1 for an unknown exit code or
+     * duration. Newer versions soft-delete rows by setting `deleted_at`.
+     */
+    let conn = open(path)?;
+    let has_deleted = conn
+        .prepare("SELECT * FROM history LIMIT 0")?
+        .column_names()
+        .contains(&"deleted_at");
+    let query = format!(
+        "SELECT command, timestamp, cwd, exit, duration FROM history {} ORDER BY timestamp",
+        if has_deleted {
+            "WHERE deleted_at IS NULL"
+        } else {
+            ""
+        }
+    );
+    let mut statement = conn.prepare(&query)?;
+    let rows = statement.query_map([], |row| {
+        let timestamp: i64 = row.get(1)?;
+        let exit: i64 = row.get(3)?;
+        let duration: i64 = row.get(4)?;
+        Ok(Command {
+            command: row.get(0)?,
+            timestamp: u64::try_from(timestamp / 1_000_000_000).ok(),
+            context: Context {
+                cwd: row.get(2)?,
+                exit: i32::try_from(exit).ok().filter(|exit| *exit >= 0),
+                duration: u64::try_from(duration)
+                    .ok()
+                    .map(|duration| duration / 1_000_000),
+            },
+        })
+    })?;
+    rows.collect()
+}
+
+fn read_mcfly(path: &Path) -> rusqlite::Result<Vec<Command>> {
+    // mcfly records when each command was run, in seconds, but not how long it took
+    let conn = open(path)?;
+    let mut statement =
+        conn.prepare("SELECT cmd, when_run, dir, exit_code FROM commands ORDER BY when_run, id")?;
+    let rows = statement.query_map([], |row| {
+        let when_run: Option<i64> = row.get(1)?;
+        let exit: Option<i64> = row.get(3)?;
+        Ok(Command {
+            command: row.get(0)?,
+            timestamp: when_run.and_then(|time| u64::try_from(time).ok()),
+            context: Context {
+                cwd: row.get(2)?,
+                exit: exit.and_then(|exit| i32::try_from(exit).ok()),
+                duration: None,
+            },
+        })
+    })?;
+    rows.collect()
+}
+
+fn read_resh(path: &Path) -> io::Result<Vec<Command>> {
+    /* resh writes one JSON record per line. Current versions store the time
+     * and duration as strings of seconds ("time", "duration"), older ones as
+     * numbers ("realtimeBefore", "realtimeDuration").
+     */
+    let seconds = |record: &Value, keys: [&str; 2]| {
+        keys.iter().find_map(|key| match record.get(key)? {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        })
+    };
+
+    let contents = fs::read_to_string(path)?;
+    let mut commands = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(line).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path.display(), number + 1, err),
+            )
+        })?;
+        let Some(command) = record.get("cmdLine").and_then(Value::as_str) else {
+            continue;
+        };
+        commands.push(Command {
+            command: command.to_string(),
+            timestamp: seconds(&record, ["time", "realtimeBefore"]).map(|time| time as u64),
+            context: Context {
+                cwd: record
+                    .get("pwd")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                exit: record
+                    .get("exitCode")
+                    .and_then(Value::as_i64)
+                    .and_then(|exit| i32::try_from(exit).ok()),
+                duration: seconds(&record, ["duration", "realtimeDuration"])
+                    .map(|duration| (duration * 1000.0) as u64),
+            },
+        });
+    }
+    commands.sort_by_key(|command| command.timestamp);
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn reads_atuin() {
+        let commands = Tool::Atuin.read(&fixture("atuin.db")).unwrap();
+        let lines: Vec<&str> = commands.iter().map(|c| c.command.as_str()).collect();
+        // The deleted row is skipped and the rest are in time order
+        assert_eq!(lines, ["cd ~/src/hui", "cargo test", "git push"]);
+        let test = &commands[1];
+        assert_eq!(test.timestamp, Some(1_700_000_060));
+        assert_eq!(test.context.cwd.as_deref(), Some("/home/me/src/hui"));
+        assert_eq!(test.context.exit, Some(101));
+        assert_eq!(test.context.duration, Some(2_500));
+        // An unfinished command has no exit code or duration
+        assert_eq!(commands[2].context.exit, None);
+        assert_eq!(commands[2].context.duration, None);
+    }
+
+    #[test]
+    fn reads_mcfly() {
+        let commands = Tool::Mcfly.read(&fixture("mcfly.db")).unwrap();
+        let lines: Vec<&str> = commands.iter().map(|c| c.command.as_str()).collect();
+        assert_eq!(lines, ["ls -la", "make", "make install"]);
+        assert_eq!(commands[1].timestamp, Some(1_600_000_100));
+        assert_eq!(commands[1].context.cwd.as_deref(), Some("/tmp/project"));
+        assert_eq!(commands[1].context.exit, Some(2));
+        assert_eq!(commands[1].context.duration, None);
+    }
+
+    #[test]
+    fn reads_resh() {
+        let commands = Tool::Resh.read(&fixture("resh.reshjson")).unwrap();
+        let lines: Vec<&str> = commands.iter().map(|c| c.command.as_str()).collect();
+        // Both the current and the older record layout are understood
+        assert_eq!(lines, ["vim notes.md", "kubectl get pods"]);
+        assert_eq!(commands[0].timestamp, Some(1_650_000_000));
+        assert_eq!(commands[0].context.duration, Some(1_250));
+        assert_eq!(commands[1].context.cwd.as_deref(), Some("/home/me"));
+        assert_eq!(commands[1].context.exit, Some(1));
+        assert_eq!(commands[1].context.duration, Some(300));
+    }
+
+    #[test]
+    fn imported_commands_round_trip_through_the_data_file() {
+        let commands = Tool::Atuin.read(&fixture("atuin.db")).unwrap();
+        let json = serde_json::to_vec(&commands).unwrap();
+        let read: Vec<Command> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(read.len(), commands.len());
+        assert_eq!(read[1].context.exit, Some(101));
+        assert_eq!(read[1].timestamp, Some(1_700_000_060));
+    }
+
+    #[test]
+    fn missing_database_is_an_error() {
+        assert!(Tool::Mcfly.read(&fixture("missing.db")).is_err());
+    }
+}
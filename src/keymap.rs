@@ -0,0 +1,572 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+
+/// The set of bindings that is active, depending on what has focus.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Browsing the results list.
+    Normal,
+    /// Typing in the search box.
+    Editing,
+    /// Changing the selected command in the edit popup.
+    Edit,
+    /// Choosing the placeholders of a new template.
+    Template,
+    /// Filling in the placeholders of a template.
+    Form,
+    /// Editing the note and tags of a favorite.
+    Note,
+    /// Confirming that a dangerous command is run.
+    Confirm,
+    /// The help overlay is open.
+    Help,
+    /// The statistics view is open.
+    Stats,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 9] = [
+        Mode::Normal,
+        Mode::Editing,
+        Mode::Edit,
+        Mode::Template,
+        Mode::Form,
+        Mode::Note,
+        Mode::Confirm,
+        Mode::Help,
+        Mode::Stats,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "Results list",
+            Mode::Editing => "Search box",
+            Mode::Edit => "Edit command",
+            Mode::Template => "New template",
+            Mode::Form => "Fill in template",
+            Mode::Note => "Favorite note and tags",
+            Mode::Confirm => "Run dangerous command",
+            Mode::Help => "Help",
+            Mode::Stats => "Statistics",
+        }
+    }
+}
+
+/// Everything a key can be bound to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    // Results list
+    StartSearch,
+    Accept,
+    Execute,
+    Quit,
+    Next,
+    Previous,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    First,
+    Last,
+    JumpDigit,
+    EditSelected,
+    MakeTemplate,
+    ToggleStar,
+    EditNote,
+    ToggleFavorites,
+    CycleSource,
+    ToggleIgnored,
+    ToggleExplain,
+    ToggleGroups,
+    ExpandGroup,
+    CollapseGroup,
+
+    // Search box
+    InsertChar,
+    DeleteChar,
+    CursorLeft,
+    CursorRight,
+    CursorStart,
+    CursorEnd,
+    WordLeft,
+    WordRight,
+    DeleteWord,
+    DeleteLine,
+    FinishSearch,
+    CancelSearch,
+
+    // Edit popup
+    CursorUp,
+    CursorDown,
+    NewLine,
+    AcceptEdit,
+    CancelEdit,
+
+    // Templates
+    TogglePlaceholder,
+    SaveTemplate,
+    CancelTemplate,
+    NextField,
+    PreviousField,
+    NextSuggestion,
+    AcceptForm,
+    CancelForm,
+
+    // Favorites
+    SaveNote,
+    CancelNote,
+
+    // Dangerous commands
+    ConfirmRun,
+    CancelRun,
+
+    // Anywhere
+    CycleSearchMode,
+    ToggleSort,
+    ShowHelp,
+
+    // Help overlay
+    CloseHelp,
+    ScrollHelpDown,
+    ScrollHelpUp,
+
+    // Statistics
+    ShowStats,
+    CloseStats,
+}
+
+impl Action {
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::StartSearch => "Filter results",
+            Action::Accept => "Copy selected command and exit (or jump to typed number)",
+            Action::Execute => "Run selected command and exit, asking first if it is dangerous",
+            Action::Quit => "Exit without copying",
+            Action::Next => "Select next entry",
+            Action::Previous => "Select previous entry",
+            Action::PageDown => "Page down",
+            Action::PageUp => "Page up",
+            Action::HalfPageDown => "Half a page down",
+            Action::HalfPageUp => "Half a page up",
+            Action::First => "Jump to first entry",
+            Action::Last => "Jump to last entry (or to typed number)",
+            Action::JumpDigit => "Type an entry number to jump to",
+            Action::EditSelected => "Edit selected command before copying it",
+            Action::MakeTemplate => "Save selected command as a template",
+            Action::ToggleStar => "Star or unstar selected command as a favorite",
+            Action::EditNote => "Add a note and tags to selected command, starring it",
+            Action::ToggleFavorites => "Show only favorites, or everything again",
+            Action::CycleSource => "Show only entries from the next history source",
+            Action::ToggleIgnored => {
+                "Reveal entries hidden by the ignore rules, or hide them again"
+            }
+            Action::ToggleExplain => "Explain the flags of selected command from its man page",
+            Action::ToggleGroups => "Group entries by program and subcommand, or list them all",
+            Action::ExpandGroup => "Expand selected group",
+            Action::CollapseGroup => "Collapse selected group, or the group of selected entry",
+            Action::InsertChar => "Type a character",
+            Action::DeleteChar => "Delete previous character",
+            Action::CursorLeft => "Move cursor left",
+            Action::CursorRight => "Move cursor right",
+            Action::CursorStart => "Move cursor to start of line",
+            Action::CursorEnd => "Move cursor to end of line",
+            Action::WordLeft => "Move cursor back a word",
+            Action::WordRight => "Move cursor forward a word",
+            Action::DeleteWord => "Delete previous word",
+            Action::DeleteLine => "Delete whole search",
+            Action::FinishSearch => "Back to the results list",
+            Action::CancelSearch => "Clear search and go back to the results list",
+            Action::CursorUp => "Move cursor up",
+            Action::CursorDown => "Move cursor down",
+            Action::NewLine => "Insert a new line",
+            Action::AcceptEdit => "Accept edited command and exit",
+            Action::CancelEdit => "Discard changes",
+            Action::TogglePlaceholder => "Mark or unmark the word as a placeholder",
+            Action::SaveTemplate => "Save the template",
+            Action::CancelTemplate => "Cancel without saving",
+            Action::NextSuggestion => "Use the next previously used value",
+            Action::AcceptForm => "Accept the filled in command and exit",
+            Action::CancelForm => "Go back to the results list",
+            Action::NextField => "Next field",
+            Action::PreviousField => "Previous field",
+            Action::SaveNote => "Save note and tags",
+            Action::CancelNote => "Discard changes",
+            Action::ConfirmRun => "Run the command anyway",
+            Action::CancelRun => "Go back without running it",
+            Action::CycleSearchMode => "Cycle search mode (fuzzy, substring, regex)",
+            Action::ToggleSort => "Toggle sort order (relevance, recency)",
+            Action::ShowHelp => "Show this help",
+            Action::CloseHelp => "Close help",
+            Action::ScrollHelpDown => "Scroll help down",
+            Action::ScrollHelpUp => "Scroll help up",
+            Action::ShowStats => "Show usage statistics",
+            Action::CloseStats => "Close statistics",
+        }
+    }
+}
+
+/// A key, or class of keys, that can trigger an action.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Code(KeyCode, KeyModifiers),
+    AnyDigit,
+    AnyChar,
+}
+
+impl Key {
+    fn plain(code: KeyCode) -> Key {
+        Key::Code(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> Key {
+        Key::Code(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn alt(c: char) -> Key {
+        Key::Code(KeyCode::Char(c), KeyModifiers::ALT)
+    }
+
+    fn char(c: char) -> Key {
+        Key::plain(KeyCode::Char(c))
+    }
+
+    /// Whether `event` presses exactly this key. Shift is ignored for
+    /// characters, since it is already part of the character itself.
+    fn matches(&self, event: &KeyEvent) -> bool {
+        let mut pressed = event.modifiers;
+        if let KeyCode::Char(_) = event.code {
+            pressed.remove(KeyModifiers::SHIFT);
+        }
+        match *self {
+            Key::Code(code, modifiers) => code == event.code && modifiers == pressed,
+            Key::AnyDigit => {
+                matches!(event.code, KeyCode::Char('0'..='9')) && pressed == KeyModifiers::NONE
+            }
+            // Windows reports characters typed with AltGr, like `@` on a German
+            // keyboard, as Ctrl-Alt; bound Ctrl and Alt keys are matched first
+            Key::AnyChar => {
+                matches!(event.code, KeyCode::Char(_))
+                    && (pressed == KeyModifiers::NONE
+                        || pressed == KeyModifiers::CONTROL | KeyModifiers::ALT)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (code, modifiers) = match *self {
+            Key::Code(code, modifiers) => (code, modifiers),
+            Key::AnyDigit => return write!(f, "0-9"),
+            Key::AnyChar => return write!(f, "any character"),
+        };
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        match code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::BackTab => write!(f, "Shift-Tab"),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+pub struct Binding {
+    pub mode: Mode,
+    pub action: Action,
+    pub keys: Vec<Key>,
+}
+
+/// Maps key presses to actions for each mode. The help overlay is generated
+/// from the same bindings, so it always lists what the keys actually do.
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        use Action::*;
+
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+
+        keymap.bind(Mode::Normal, StartSearch, vec![Key::char('/')]);
+        keymap.bind(Mode::Normal, Accept, vec![Key::plain(KeyCode::Enter)]);
+        keymap.bind(
+            Mode::Normal,
+            Execute,
+            vec![Key::char('r'), Key::Code(KeyCode::Enter, KeyModifiers::ALT)],
+        );
+        keymap.bind(Mode::Normal, Quit, vec![Key::char('q')]);
+        keymap.bind(Mode::Normal, Next, vec![Key::plain(KeyCode::Down)]);
+        keymap.bind(Mode::Normal, Previous, vec![Key::plain(KeyCode::Up)]);
+        keymap.bind(Mode::Normal, PageDown, vec![Key::plain(KeyCode::PageDown)]);
+        keymap.bind(Mode::Normal, PageUp, vec![Key::plain(KeyCode::PageUp)]);
+        keymap.bind(Mode::Normal, HalfPageDown, vec![Key::ctrl('d')]);
+        keymap.bind(Mode::Normal, HalfPageUp, vec![Key::ctrl('u')]);
+        keymap.bind(
+            Mode::Normal,
+            First,
+            vec![Key::plain(KeyCode::Home), Key::char('g')],
+        );
+        keymap.bind(
+            Mode::Normal,
+            Last,
+            vec![Key::plain(KeyCode::End), Key::char('G')],
+        );
+        keymap.bind(Mode::Normal, JumpDigit, vec![Key::AnyDigit]);
+        keymap.bind(Mode::Normal, EditSelected, vec![Key::char('e')]);
+        keymap.bind(Mode::Normal, MakeTemplate, vec![Key::char('t')]);
+        keymap.bind(Mode::Normal, ToggleStar, vec![Key::char('*')]);
+        keymap.bind(Mode::Normal, EditNote, vec![Key::char('n')]);
+        keymap.bind(Mode::Normal, ToggleFavorites, vec![Key::char('f')]);
+        keymap.bind(Mode::Normal, CycleSource, vec![Key::char('s')]);
+        keymap.bind(Mode::Normal, ToggleIgnored, vec![Key::char('i')]);
+        keymap.bind(Mode::Normal, ToggleExplain, vec![Key::char('x')]);
+        keymap.bind(Mode::Normal, ToggleGroups, vec![Key::char('v')]);
+        keymap.bind(Mode::Normal, ExpandGroup, vec![Key::plain(KeyCode::Right)]);
+        keymap.bind(Mode::Normal, CollapseGroup, vec![Key::plain(KeyCode::Left)]);
+        keymap.bind(Mode::Normal, ShowStats, vec![Key::char('S')]);
+        keymap.bind(Mode::Normal, CycleSearchMode, vec![Key::ctrl('t')]);
+        keymap.bind(Mode::Normal, ToggleSort, vec![Key::ctrl('o')]);
+        keymap.bind(
+            Mode::Normal,
+            ShowHelp,
+            vec![Key::char('?'), Key::plain(KeyCode::F(1))],
+        );
+
+        // Metacharacter examples: Opt + Arrows to jump by word
+        //                         Opt + Backspace to delete by word
+        //                         Cmd + Arrows to jump to beginning and end
+        //                         Cmd + Backspace to delete everything
+        keymap.bind(
+            Mode::Editing,
+            Execute,
+            vec![Key::Code(KeyCode::Enter, KeyModifiers::ALT)],
+        );
+        keymap.bind(Mode::Editing, CursorStart, vec![Key::ctrl('a')]);
+        keymap.bind(Mode::Editing, CursorEnd, vec![Key::ctrl('e')]);
+        keymap.bind(Mode::Editing, WordLeft, vec![Key::alt('b')]);
+        keymap.bind(Mode::Editing, WordRight, vec![Key::alt('f')]);
+        keymap.bind(Mode::Editing, DeleteLine, vec![Key::ctrl('u')]);
+        // @TODO/fix Alt+Backspace arrives as Ctrl+w rather than with KeyModifiers::ALT
+        keymap.bind(Mode::Editing, DeleteWord, vec![Key::ctrl('w')]);
+        keymap.bind(Mode::Editing, CycleSearchMode, vec![Key::ctrl('t')]);
+        keymap.bind(Mode::Editing, ToggleSort, vec![Key::ctrl('o')]);
+        keymap.bind(Mode::Editing, ShowHelp, vec![Key::plain(KeyCode::F(1))]);
+        keymap.bind(
+            Mode::Editing,
+            FinishSearch,
+            vec![
+                Key::plain(KeyCode::Enter),
+                Key::plain(KeyCode::Up),
+                Key::plain(KeyCode::Down),
+            ],
+        );
+        keymap.bind(Mode::Editing, CursorLeft, vec![Key::plain(KeyCode::Left)]);
+        keymap.bind(Mode::Editing, CursorRight, vec![Key::plain(KeyCode::Right)]);
+        keymap.bind(
+            Mode::Editing,
+            DeleteChar,
+            vec![Key::plain(KeyCode::Backspace)],
+        );
+        keymap.bind(Mode::Editing, CancelSearch, vec![Key::plain(KeyCode::Esc)]);
+        keymap.bind(Mode::Editing, InsertChar, vec![Key::AnyChar]);
+
+        keymap.bind(Mode::Edit, AcceptEdit, vec![Key::plain(KeyCode::Enter)]);
+        keymap.bind(
+            Mode::Edit,
+            NewLine,
+            vec![Key::Code(KeyCode::Enter, KeyModifiers::ALT), Key::ctrl('j')],
+        );
+        keymap.bind(Mode::Edit, CancelEdit, vec![Key::plain(KeyCode::Esc)]);
+        keymap.bind(Mode::Edit, CursorLeft, vec![Key::plain(KeyCode::Left)]);
+        keymap.bind(Mode::Edit, CursorRight, vec![Key::plain(KeyCode::Right)]);
+        keymap.bind(Mode::Edit, CursorUp, vec![Key::plain(KeyCode::Up)]);
+        keymap.bind(Mode::Edit, CursorDown, vec![Key::plain(KeyCode::Down)]);
+        keymap.bind(
+            Mode::Edit,
+            CursorStart,
+            vec![Key::plain(KeyCode::Home), Key::ctrl('a')],
+        );
+        keymap.bind(
+            Mode::Edit,
+            CursorEnd,
+            vec![Key::plain(KeyCode::End), Key::ctrl('e')],
+        );
+        keymap.bind(Mode::Edit, WordLeft, vec![Key::alt('b')]);
+        keymap.bind(Mode::Edit, WordRight, vec![Key::alt('f')]);
+        keymap.bind(Mode::Edit, DeleteWord, vec![Key::ctrl('w')]);
+        keymap.bind(Mode::Edit, DeleteChar, vec![Key::plain(KeyCode::Backspace)]);
+        keymap.bind(Mode::Edit, ShowHelp, vec![Key::plain(KeyCode::F(1))]);
+        keymap.bind(Mode::Edit, InsertChar, vec![Key::AnyChar]);
+
+        keymap.bind(Mode::Template, CursorLeft, vec![Key::plain(KeyCode::Left)]);
+        keymap.bind(
+            Mode::Template,
+            CursorRight,
+            vec![Key::plain(KeyCode::Right)],
+        );
+        keymap.bind(Mode::Template, TogglePlaceholder, vec![Key::char(' ')]);
+        keymap.bind(
+            Mode::Template,
+            SaveTemplate,
+            vec![Key::plain(KeyCode::Enter)],
+        );
+        keymap.bind(
+            Mode::Template,
+            CancelTemplate,
+            vec![Key::plain(KeyCode::Esc)],
+        );
+        keymap.bind(Mode::Template, ShowHelp, vec![Key::plain(KeyCode::F(1))]);
+
+        keymap.bind(Mode::Form, AcceptForm, vec![Key::plain(KeyCode::Enter)]);
+        keymap.bind(Mode::Form, CancelForm, vec![Key::plain(KeyCode::Esc)]);
+        keymap.bind(Mode::Form, NextField, vec![Key::plain(KeyCode::Down)]);
+        keymap.bind(
+            Mode::Form,
+            PreviousField,
+            vec![Key::plain(KeyCode::Up), Key::plain(KeyCode::BackTab)],
+        );
+        keymap.bind(Mode::Form, NextSuggestion, vec![Key::plain(KeyCode::Tab)]);
+        keymap.bind(Mode::Form, DeleteChar, vec![Key::plain(KeyCode::Backspace)]);
+        keymap.bind(Mode::Form, DeleteLine, vec![Key::ctrl('u')]);
+        keymap.bind(Mode::Form, ShowHelp, vec![Key::plain(KeyCode::F(1))]);
+        keymap.bind(Mode::Form, InsertChar, vec![Key::AnyChar]);
+
+        keymap.bind(Mode::Note, SaveNote, vec![Key::plain(KeyCode::Enter)]);
+        keymap.bind(Mode::Note, CancelNote, vec![Key::plain(KeyCode::Esc)]);
+        keymap.bind(
+            Mode::Note,
+            NextField,
+            vec![Key::plain(KeyCode::Tab), Key::plain(KeyCode::Down)],
+        );
+        keymap.bind(
+            Mode::Note,
+            PreviousField,
+            vec![Key::plain(KeyCode::BackTab), Key::plain(KeyCode::Up)],
+        );
+        keymap.bind(Mode::Note, DeleteChar, vec![Key::plain(KeyCode::Backspace)]);
+        keymap.bind(Mode::Note, DeleteLine, vec![Key::ctrl('u')]);
+        keymap.bind(Mode::Note, ShowHelp, vec![Key::plain(KeyCode::F(1))]);
+        keymap.bind(Mode::Note, InsertChar, vec![Key::AnyChar]);
+
+        keymap.bind(Mode::Confirm, ConfirmRun, vec![Key::char('y')]);
+        keymap.bind(
+            Mode::Confirm,
+            CancelRun,
+            vec![Key::char('n'), Key::plain(KeyCode::Esc), Key::char('q')],
+        );
+        keymap.bind(Mode::Confirm, ShowHelp, vec![Key::plain(KeyCode::F(1))]);
+
+        keymap.bind(
+            Mode::Help,
+            CloseHelp,
+            vec![
+                Key::plain(KeyCode::Esc),
+                Key::char('q'),
+                Key::char('?'),
+                Key::plain(KeyCode::F(1)),
+            ],
+        );
+        keymap.bind(Mode::Help, ScrollHelpDown, vec![Key::plain(KeyCode::Down)]);
+        keymap.bind(Mode::Help, ScrollHelpUp, vec![Key::plain(KeyCode::Up)]);
+
+        keymap.bind(
+            Mode::Stats,
+            CloseStats,
+            vec![Key::plain(KeyCode::Esc), Key::char('q'), Key::char('S')],
+        );
+        keymap.bind(
+            Mode::Stats,
+            ShowHelp,
+            vec![Key::char('?'), Key::plain(KeyCode::F(1))],
+        );
+
+        keymap
+    }
+}
+
+impl Keymap {
+    fn bind(&mut self, mode: Mode, action: Action, keys: Vec<Key>) {
+        self.bindings.push(Binding { mode, action, keys });
+    }
+
+    /// Returns the action `event` is bound to in `mode`.
+    pub fn action(&self, mode: Mode, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|b| b.mode == mode)
+            .find(|b| b.keys.iter().any(|key| key.matches(event)))
+            .map(|b| b.action)
+    }
+
+    /// The bindings of `mode`, in the order they were bound.
+    pub fn bindings(&self, mode: Mode) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter().filter(move |b| b.mode == mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn modifiers_have_to_be_bound() {
+        let keymap = Keymap::default();
+        let q = |modifiers| keymap.action(Mode::Normal, &press(KeyCode::Char('q'), modifiers));
+        assert_eq!(q(KeyModifiers::NONE), Some(Action::Quit));
+        assert_eq!(q(KeyModifiers::CONTROL), None);
+        assert_eq!(
+            keymap.action(Mode::Normal, &press(KeyCode::Char('e'), KeyModifiers::ALT)),
+            None
+        );
+        assert_eq!(
+            keymap.action(
+                Mode::Editing,
+                &press(KeyCode::Char('z'), KeyModifiers::CONTROL)
+            ),
+            None
+        );
+
+        // Shift is part of the character, so `G` is bound as itself
+        let shift_g = press(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.action(Mode::Normal, &shift_g), Some(Action::Last));
+        assert_eq!(
+            keymap.action(
+                Mode::Editing,
+                &press(KeyCode::Char('A'), KeyModifiers::SHIFT)
+            ),
+            Some(Action::InsertChar)
+        );
+
+        // AltGr characters arrive as Ctrl-Alt on Windows
+        let alt_gr = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        assert_eq!(
+            keymap.action(Mode::Editing, &press(KeyCode::Char('@'), alt_gr)),
+            Some(Action::InsertChar)
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! The parts of `hui` that don't need a terminal: reading and merging shell
+//! histories, searching and ranking them, and the state of the list the
+//! interface picks a command from. The `hui` binary adds the rest: the
+//! command line, the configuration and drawing the interface and handling
+//! its keys.
+//!
+//! - [`history`] reads zsh, bash and fish history files and merges them into
+//!   one timeline. Sources other than files are added by implementing
+//!   [`history::HistorySource`]. [`zsh`] is the zsh format on its own.
+//! - [`search`] filters and ranks the history the way the search box does,
+//!   and [`period`] narrows it to when commands were run.
+//! - [`shell`] splits commands into their parts, for highlighting them, and
+//!   [`explain`] describes those parts with the local man and tldr pages.
+//! - [`picker`] keeps which entry is selected and how far the list is scrolled,
+//!   and groups it by program and subcommand.
+//! - [`ignore`], [`danger`], [`favorites`] and [`template`] are the data hui
+//!   keeps in its data directory, which [`store`] reads and writes.
+//! - [`stats`], [`export`], [`import`] and [`query`] are behind the `hui`
+//!   subcommands.
+//! - [`load`] and [`watch`] read the history in the background and keep it
+//!   up to date.
+//! - [`sync`] shares the history with other machines, encrypted with
+//!   [`crypto`] if wanted.
+//!
+//! Reading a history and searching it:
+//!
+//! ```no_run
+//! use fuzzy_matcher::skim::SkimMatcherV2;
+//! use hui::history::{self, Dedup, HistorySource, Source};
+//! use hui::ignore::Ignore;
+//! use hui::search::{self, SearchMode, SortOrder};
+//! use std::borrow::Cow;
+//!
+//! let source = Source::parse("zsh");
+//! let history = history::merge(&[source.read()?], Dedup::Latest, &Ignore::default());
+//! let matches = search::filter(
+//!     &SkimMatcherV2::default(),
+//!     &history,
+//!     |entry| Cow::Borrowed(&entry.command),
+//!     "docker run",
+//!     SearchMode::Fuzzy,
+//!     SortOrder::Relevance,
+//! )?;
+//! for entry in matches {
+//!     println!("{}", entry.command);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! A source of your own, searched along with the shell histories:
+//!
+//! ```
+//! use hui::history::{Command, HistorySource};
+//! use std::io;
+//!
+//! struct Runbook(Vec<&'static str>);
+//!
+//! impl HistorySource for Runbook {
+//!     fn label(&self) -> &str {
+//!         "runbook"
+//!     }
+//!
+//!     fn name(&self) -> &str {
+//!         "team runbook"
+//!     }
+//!
+//!     fn read(&self) -> io::Result<Vec<Command>> {
+//!         Ok(self.0.iter().map(|c| Command::new(c.to_string(), None)).collect())
+//!     }
+//! }
+//!
+//! let runbook = Runbook(vec!["systemctl restart nginx"]);
+//! assert_eq!(runbook.read().unwrap()[0].command, "systemctl restart nginx");
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod crypto;
+pub mod danger;
+pub mod explain;
+pub mod export;
+pub mod favorites;
+pub mod history;
+pub mod ignore;
+pub mod import;
+pub mod load;
+pub mod period;
+pub mod picker;
+pub mod query;
+pub mod search;
+pub mod shell;
+pub mod stats;
+pub mod store;
+pub mod sync;
+pub mod template;
+pub mod watch;
+pub mod zsh;
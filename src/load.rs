@@ -0,0 +1,43 @@
+//! Reading history sources in the background, newest commands first.
+
+use crate::history::{Command, HistorySource};
+use std::fs;
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+/// Part of a history source, read by `start`.
+pub enum Loaded {
+    /// Commands older than any sent before for the source.
+    Older(usize, Vec<Command>),
+    /// The source has been read, or why it couldn't be.
+    Done(usize, io::Result<()>),
+}
+
+/// Starts reading every source on a thread of its own, sending its most recent
+/// commands first, so that the list fills in while large files are still
+/// being read. Also returns how much of each source is read, so that what
+/// gets appended to it later can be read from there.
+pub fn start(sources: &[Arc<dyn HistorySource>]) -> (Receiver<Loaded>, Vec<u64>) {
+    let (sender, receiver) = mpsc::channel();
+    let mut lengths = Vec::new();
+    for (index, source) in sources.iter().enumerate() {
+        let len = source
+            .path()
+            .and_then(|path| fs::metadata(path).ok())
+            .map_or(0, |metadata| metadata.len());
+        lengths.push(len);
+
+        let source = Arc::clone(source);
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let read = source.read_backwards(len, &mut |commands| {
+                // Nothing is left to send them to once hui has quit
+                let _ = sender.send(Loaded::Older(index, commands));
+            });
+            let _ = sender.send(Loaded::Done(index, read));
+        });
+    }
+    (receiver, lengths)
+}
@@ -11,14 +11,22 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{
+        BarChart, Block, Borders, Clear, List, ListItem, ListState, Paragraph, Sparkline, Wrap,
+    },
     Frame, Terminal,
 };
 use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     env,
     error::Error,
-    io,
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+    process,
     rc::Rc,
+    sync::{mpsc::Receiver, Arc},
     time::{Duration, Instant},
 };
 
@@ -26,73 +34,81 @@ use copypasta::{ClipboardContext, ClipboardProvider};
 use unicode_width::UnicodeWidthStr;
 
 use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
 
-struct StatefulList<T: Default> {
-    state: ListState,
-    items: Vec<T>,
-}
+mod cli;
+mod config;
+mod keymap;
+mod textarea;
+mod theme;
 
-enum InputMode {
-    Normal,
-    Editing,
-}
+use chrono::{Local, TimeZone};
+use clap::Parser;
+use cli::{Args, Commands, KeyCommand};
+use config::{Config, Output};
+use hui::crypto::Key;
+use hui::danger::Danger;
+use hui::explain::Explainer;
+use hui::favorites::{self, Favorite, NoteForm};
+use hui::history::{self, Command, Dedup, Entry, HistorySource, Source};
+use hui::ignore::Ignore;
+use hui::load::{self, Loaded};
+use hui::period::{self, Period};
+use hui::picker::{self, Item, Level, Picker};
+use hui::search::{self, Highlight, Scope, SearchMode, SortOrder};
+use hui::shell::Kind;
+use hui::stats::{self, Stats};
+use hui::template::{self, Form, Marking, Template};
+use hui::watch::{Update, Watch};
+use hui::{export, import, query, store, sync};
+use keymap::{Action, Keymap, Mode};
+use textarea::TextArea;
+use theme::Theme;
 
-impl<T: Default> StatefulList<T> {
-    fn with_items(items: Vec<T>) -> StatefulList<T> {
-        let mut stateful_list = StatefulList {
-            state: ListState::default(),
-            items,
-        };
+// Two clicks on the same entry within this window count as a double-click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+// How often the list is updated while the history is still being read.
+const LOADING_TICK: Duration = Duration::from_millis(20);
+// The exit status that tells shell integrations to run the command printed.
+const RUN_STATUS: i32 = 10;
 
-        // Select the first element in the list
-        stateful_list.state.select(Some(0));
+/// Where the interface gets its events from: the terminal, or a script in the tests.
+trait EventSource {
+    /// Waits up to `timeout` for the next event, returning `None` when none came.
+    fn next(&mut self, timeout: Duration) -> io::Result<Option<Event>>;
+}
 
-        stateful_list
-    }
+/// The events of the terminal hui is drawn in.
+struct TerminalEvents;
 
-    fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if self.items.len() == 0 {
-                    0
-                } else if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+impl EventSource for TerminalEvents {
+    fn next(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if event::poll(timeout)? {
+            Ok(Some(event::read()?))
+        } else {
+            Ok(None)
+        }
     }
+}
 
-    fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if self.items.len() == 0 {
-                    0
-                } else if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
-    }
+enum InputMode {
+    Normal,
+    Editing,
+}
 
-    fn selected_index(&mut self) -> usize {
-        // @TODO/improvement instead of returning the
-        // index, get the actual item at that index.
-        let index = match self.state.selected() {
-            Some(i) => i,
-            None => 0,
-        };
+/// How hui exits, once a command is accepted or it is closed.
+#[derive(Debug, PartialEq)]
+enum Exit {
+    /// Prints the message, which is only the command for shell integrations.
+    Print(String),
+    /// Runs the command.
+    Run(String),
+}
 
-        index
-    }
+/// A dangerous command waiting to be confirmed before it is run.
+struct Confirm {
+    command: String,
+    // The part of the command the danger list matched
+    risky: Range<usize>,
 }
 
 /// This struct holds the current state of the app. In particular, it has the `items` field which is a wrapper
@@ -102,95 +118,1425 @@ impl<T: Default> StatefulList<T> {
 /// Check the event handling at the bottom to see how to change the state on incoming events.
 /// Check the drawing logic for items on how to specify the highlighting style for selected items.
 struct App {
-    full_history: Vec<String>,
-    items: StatefulList<String>,
+    full_history: Vec<Item>,
+    items: Picker<Item>,
+    // Whether the list is grouped by program and subcommand, and which
+    // groups are expanded.
+    grouped: bool,
+    expanded: HashSet<String>,
+    // What the search matched before it was grouped, while the list is.
+    matches: Vec<Item>,
     input: String,
+    // The search input without its `@` words, which narrow the list to
+    // `narrowed` along with the period given with --since and --until.
+    query: String,
+    // What the query matches, for highlighting it in the rows drawn
+    highlight: Highlight,
+    period: Period,
+    narrowed: Period,
     input_pos: u64,
     input_prev: String,
     input_mode: InputMode,
-    clipboard: copypasta::ClipboardContext,
+    // The system clipboard, or a stand-in in the tests.
+    clipboard: Box<dyn ClipboardProvider>,
     chunks: Rc<[Rect]>,
     fuzzy_matcher: SkimMatcherV2,
+    search_mode: SearchMode,
+    sort_order: SortOrder,
+    scope: Scope,
+    // Only entries from this history source are listed, when set.
+    source_filter: Option<usize>,
+    // What is known about each entry of the history, such as where it came from.
+    history: Vec<Entry>,
+    // The commands read from each history source, which the history is merged from.
+    histories: Vec<Vec<Command>>,
+    ignore: Ignore,
+    danger: Danger,
+    // Sends the commands of the history sources as they are read, and which
+    // sources are still being read.
+    loader: Receiver<Loaded>,
+    loading: Vec<bool>,
+    // Watches the history sources for new commands, unless that couldn't be set up.
+    watch: Option<Watch>,
+    // Short names of the history sources, shown next to their entries.
+    source_labels: Vec<String>,
+    // Entries the ignore rules hide are listed anyway while this is set.
+    show_ignored: bool,
+    ignored: usize,
+    // How long the last filter of the history took, and why it failed if it did.
+    filter_time: Option<Duration>,
+    filter_error: Option<String>,
+    // How loading each history source went, in the same order as the sources,
+    // followed by the data files.
+    sources: Vec<SourceStatus>,
+    keymap: Keymap,
+    theme: Theme,
+    // The command being changed in the edit popup, while it is open.
+    editor: Option<TextArea>,
+    templates: Vec<Template>,
+    // Saving is refused when the templates file couldn't be read, so it isn't overwritten.
+    templates_writable: bool,
+    // The template being created or filled in, while its popup is open.
+    marking: Option<Marking>,
+    form: Option<Form>,
+    favorites: Vec<Favorite>,
+    favorites_writable: bool,
+    // The favorite whose note and tags are being edited, while its popup is open.
+    note_form: Option<NoteForm>,
+    // The dangerous command about to be run, while its confirmation is open.
+    confirm: Option<Confirm>,
+    // Whether the command is run once the template form is filled in, rather than output.
+    run_form: bool,
+    // Feedback about the last action, shown in the status bar until the next key press.
+    notice: Option<String>,
+    show_help: bool,
+    help_scroll: u16,
+    // Computed when the statistics are shown, and again after the history changed
+    stats: Option<Stats>,
+    show_stats: bool,
+    // Looks up the man pages of the selected command while the explain panel is shown.
+    explainer: Explainer,
+    show_explain: bool,
+    config: Config,
+    // Entry number typed in Normal mode, jumped to with Enter or `G`.
+    pending_jump: Option<usize>,
+    // When and where the list was last clicked, to detect double-clicks.
+    last_click: Option<(Instant, usize)>,
 }
 
 impl App {
-    fn new(history: Vec<String>) -> App {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        ignore: Ignore,
+        danger: Danger,
+        source_labels: Vec<String>,
+        templates: io::Result<Vec<Template>>,
+        favorites: io::Result<Vec<Favorite>>,
+        mut sources: Vec<SourceStatus>,
+        config: Config,
+        loader: Receiver<Loaded>,
+        watch: Option<Watch>,
+        clipboard: Box<dyn ClipboardProvider>,
+    ) -> App {
+        let histories: Vec<Vec<Command>> = source_labels.iter().map(|_| Vec::new()).collect();
+        let (templates, templates_writable) = saved_data("templates", templates, &mut sources);
+        let (favorites, favorites_writable) = saved_data("favorites", favorites, &mut sources);
+
+        let history = history::merge(&histories, config.dedup, &ignore);
+        let full_history = picker::list_items(&templates, &history, &favorites);
+        // Entries the ignore rules hide aren't listed until they are revealed
+        let shown = full_history
+            .iter()
+            .filter(|item| !item.entry().is_some_and(|entry| history[entry].ignored))
+            .cloned()
+            .collect();
+
         App {
-            full_history: history.to_vec(),
-            items: StatefulList::with_items(history),
+            items: Picker::new(shown),
+            full_history,
+            grouped: false,
+            expanded: HashSet::new(),
+            matches: Vec::new(),
             input: String::new(),
+            query: String::new(),
+            highlight: Highlight::Nothing,
+            period: Period::default(),
+            narrowed: Period::default(),
             input_pos: 0,
             input_prev: String::new(),
             input_mode: InputMode::Normal,
-            clipboard: ClipboardContext::new().unwrap(),
+            clipboard,
             chunks: Rc::new([]),
             fuzzy_matcher: SkimMatcherV2::default(),
+            search_mode: SearchMode::Fuzzy,
+            sort_order: SortOrder::Relevance,
+            scope: Scope::All,
+            source_filter: None,
+            show_ignored: false,
+            ignored: history.iter().filter(|entry| entry.ignored).count(),
+            stats: None,
+            history,
+            loading: vec![true; histories.len()],
+            histories,
+            ignore,
+            danger,
+            loader,
+            watch,
+            source_labels,
+            filter_time: None,
+            filter_error: None,
+            sources,
+            keymap: Keymap::default(),
+            theme: Theme::new(config.theme),
+            editor: None,
+            templates,
+            templates_writable,
+            marking: None,
+            form: None,
+            favorites,
+            favorites_writable,
+            note_form: None,
+            confirm: None,
+            run_form: false,
+            notice: None,
+            show_help: false,
+            help_scroll: 0,
+            show_stats: false,
+            explainer: Explainer::default(),
+            show_explain: false,
+            config,
+            pending_jump: None,
+            last_click: None,
+        }
+    }
+
+    /// Number of rows available to the list, inside its borders.
+    fn page_size(&self) -> usize {
+        let height = self.chunks.first().map_or(0, |chunk| chunk.height);
+        (height.saturating_sub(2) as usize).max(1)
+    }
+
+    /// Returns the index of the list item under the given terminal cell, if any.
+    fn item_at(&self, column: u16, row: u16) -> Option<usize> {
+        let list = self.chunks.first()?;
+        // Skip the list borders
+        if column <= list.x
+            || column >= (list.x + list.width).saturating_sub(1)
+            || row <= list.y
+            || row >= (list.y + list.height).saturating_sub(1)
+        {
+            return None;
+        }
+        self.items.index_at((row - list.y - 1) as usize, |item| {
+            item_height(&item.command)
+        })
+    }
+
+    /// Returns the selected command, or an empty string when nothing matched
+    /// or a group is selected.
+    fn selected(&self) -> String {
+        match self.items.selected() {
+            Some(item) if !item.is_group() => item.command.clone(),
+            _ => "".to_string(),
+        }
+    }
+
+    /// How many entries the search matched, however they are grouped.
+    fn match_count(&self) -> usize {
+        if self.grouped {
+            self.matches.len()
+        } else {
+            self.items.items().len()
+        }
+    }
+
+    /// Expands or collapses the group named `name`, selecting its header.
+    fn expand(&mut self, name: String, expand: bool) {
+        if expand {
+            self.expanded.insert(name.clone());
+        } else {
+            self.expanded.remove(&name);
+        }
+        self.items
+            .replace(picker::group(&self.matches, &self.expanded));
+        if let Some(index) = self
+            .items
+            .items()
+            .iter()
+            .position(|item| item.is_group() && item.command == name)
+        {
+            self.items.select(index);
+        }
+    }
+
+    /// The group the selected row is in or is the header of, if the list is
+    /// grouped, with whether it is expanded.
+    fn selected_group(&self) -> Option<(String, bool)> {
+        let item = self.items.selected()?;
+        match item.level {
+            Level::Group { expanded, .. } => Some((item.command.clone(), expanded)),
+            Level::Member => Some((picker::group_name(&item.command), true)),
+            Level::Top => None,
+        }
+    }
+
+    /// Outputs the selected command, or runs it when `run` is set, and returns
+    /// how hui exits. Templates are filled in first, so this returns `None`
+    /// when one was selected.
+    fn accept(&mut self, run: bool) -> Option<Exit> {
+        // Accepting a group opens or closes it
+        if let Some(Item {
+            command,
+            level: Level::Group { expanded, .. },
+            ..
+        }) = self.items.selected()
+        {
+            self.expand(command.clone(), !expanded);
+            return None;
+        }
+        match self.items.selected().and_then(Item::template) {
+            Some(index) => {
+                self.form = Some(Form::new(index, &self.templates[index]));
+                self.run_form = run;
+                None
+            }
+            None => self.finish(self.selected(), run),
+        }
+    }
+
+    /// Outputs an accepted command, or runs it when `run` is set. A command
+    /// on the danger list is only run once confirmed, so this returns `None`
+    /// while it waits for that.
+    fn finish(&mut self, command: String, run: bool) -> Option<Exit> {
+        if !run {
+            return Some(Exit::Print(self.output(command)));
+        }
+        if command.trim().is_empty() {
+            self.notice = Some("there is no command to run".to_string());
+            return None;
+        }
+        if let Some(risky) = self.danger.find(&command) {
+            self.confirm = Some(Confirm { command, risky });
+            return None;
+        }
+        Some(Exit::Run(command))
+    }
+
+    /// Saves the template being marked, replacing any identical one, and lists it first.
+    fn save_template(&mut self) {
+        let Some(marking) = self.marking.take() else {
+            return;
+        };
+        let template = marking.template();
+        if template.placeholders.is_empty() {
+            self.notice = Some("no placeholders marked, template not saved".to_string());
+            return;
+        }
+
+        let display = template.display();
+        self.templates.retain(|t| t.display() != display);
+        self.templates.insert(0, template);
+        self.full_history = picker::list_items(&self.templates, &self.history, &self.favorites);
+        self.notice = Some(match self.save_templates() {
+            Ok(()) => "template saved".to_string(),
+            Err(err) => format!("couldn't save template: {}", err),
+        });
+        self.apply_filter();
+    }
+
+    /// Returns the favorite for `command`, if it is starred.
+    fn favorite(&self, command: &str) -> Option<&Favorite> {
+        self.favorites.iter().find(|f| f.command == command)
+    }
+
+    /// Stars or unstars the selected command.
+    fn toggle_star(&mut self) {
+        let command = self.selected();
+        if command.is_empty() {
+            return;
+        }
+        let starred = self.favorite(&command).is_some();
+        if starred {
+            self.favorites.retain(|f| f.command != command);
+        } else {
+            self.favorites.push(Favorite::new(&command));
+        }
+        self.notice = Some(match self.save_favorites() {
+            Ok(()) if starred => "removed from favorites".to_string(),
+            Ok(()) => "added to favorites".to_string(),
+            Err(err) => format!("couldn't save favorites: {}", err),
+        });
+        if self.scope == Scope::Favorites {
+            self.apply_filter();
+        }
+    }
+
+    /// Saves the note and tags being edited, starring the command if needed.
+    fn save_note(&mut self) {
+        let Some(form) = self.note_form.take() else {
+            return;
+        };
+        let favorite = form.favorite();
+        match self
+            .favorites
+            .iter_mut()
+            .find(|f| f.command == favorite.command)
+        {
+            Some(existing) => *existing = favorite,
+            None => self.favorites.push(favorite),
+        }
+        self.notice = Some(match self.save_favorites() {
+            Ok(()) => "favorite saved".to_string(),
+            Err(err) => format!("couldn't save favorites: {}", err),
+        });
+    }
+
+    fn save_favorites(&self) -> io::Result<()> {
+        if !self.favorites_writable {
+            return Err(io::Error::other(
+                "the favorites file couldn't be read, not overwriting it",
+            ));
+        }
+        store::save(favorites::FILE, &self.favorites)
+    }
+
+    fn save_templates(&self) -> io::Result<()> {
+        if !self.templates_writable {
+            return Err(io::Error::other(
+                "the templates file couldn't be read, not overwriting it",
+            ));
+        }
+        store::save(template::FILE, &self.templates)
+    }
+
+    /// Sends an accepted command where `HUI_OUTPUT` says, and returns the message printed on exit.
+    fn output(&mut self, val: String) -> String {
+        match self.config.output {
+            Output::Clipboard => {
+                // Copy the text to the clipboard before quitting
+                self.clipboard.set_contents(val.clone()).unwrap();
+                format!("Copied to clipboard: {}", val)
+            }
+            // Printed as is, for shell integrations to pick up
+            Output::Stdout => val,
+        }
+    }
+
+    /// The keymap mode matching what currently has focus.
+    fn key_mode(&self) -> keymap::Mode {
+        if self.show_help {
+            return keymap::Mode::Help;
+        }
+        if self.confirm.is_some() {
+            return keymap::Mode::Confirm;
+        }
+        if self.show_stats {
+            return keymap::Mode::Stats;
+        }
+        if self.form.is_some() {
+            return keymap::Mode::Form;
+        }
+        if self.marking.is_some() {
+            return keymap::Mode::Template;
+        }
+        if self.note_form.is_some() {
+            return keymap::Mode::Note;
+        }
+        if self.editor.is_some() {
+            return keymap::Mode::Edit;
+        }
+        match self.input_mode {
+            InputMode::Normal => keymap::Mode::Normal,
+            InputMode::Editing => keymap::Mode::Editing,
+        }
+    }
+
+    /// Performs `action`, triggered by `key`. Returns how hui exits when the
+    /// action exits it.
+    fn handle_action(&mut self, action: Action, key: KeyEvent) -> Option<Exit> {
+        // A typed entry number only applies to the key right after it
+        let pending_jump = self.pending_jump.take();
+        self.notice = None;
+
+        // Text editing actions apply to the popup that is open
+        match self.key_mode() {
+            keymap::Mode::Edit => {
+                if let Some(editor) = self.editor.as_mut() {
+                    if edit_text(editor, action, key) {
+                        return None;
+                    }
+                }
+            }
+            keymap::Mode::Template => {
+                if let Some(marking) = self.marking.as_mut() {
+                    match action {
+                        Action::CursorLeft => marking.previous(),
+                        Action::CursorRight => marking.next(),
+                        _ => {}
+                    }
+                    if matches!(action, Action::CursorLeft | Action::CursorRight) {
+                        return None;
+                    }
+                }
+            }
+            keymap::Mode::Note => {
+                if let Some(form) = self.note_form.as_mut() {
+                    match action {
+                        Action::InsertChar => {
+                            if let KeyCode::Char(c) = key.code {
+                                form.value_mut().push(c);
+                            }
+                        }
+                        Action::DeleteChar => {
+                            form.value_mut().pop();
+                        }
+                        Action::DeleteLine => form.value_mut().clear(),
+                        Action::NextField | Action::PreviousField => form.switch_field(),
+                        _ => {}
+                    }
+                    if matches!(
+                        action,
+                        Action::InsertChar
+                            | Action::DeleteChar
+                            | Action::DeleteLine
+                            | Action::NextField
+                            | Action::PreviousField
+                    ) {
+                        return None;
+                    }
+                }
+            }
+            keymap::Mode::Form => {
+                if let Some(form) = self.form.as_mut() {
+                    match action {
+                        Action::InsertChar => {
+                            if let KeyCode::Char(c) = key.code {
+                                form.insert(c);
+                            }
+                        }
+                        Action::DeleteChar => form.backspace(),
+                        Action::DeleteLine => form.clear(),
+                        _ => {}
+                    }
+                    if matches!(
+                        action,
+                        Action::InsertChar | Action::DeleteChar | Action::DeleteLine
+                    ) {
+                        return None;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        match action {
+            // Results list
+            Action::StartSearch => {
+                self.input = "".to_string();
+                self.input_pos = 0;
+                self.input_mode = InputMode::Editing;
+            }
+            Action::Accept => match pending_jump {
+                // Entries are numbered from 1 in the list
+                Some(number) => self.items.select(number.saturating_sub(1)),
+                None => return self.accept(false),
+            },
+            Action::Execute => return self.accept(true),
+            Action::Quit => return Some(Exit::Print("".to_string())),
+            Action::Next => self.items.next(),
+            Action::Previous => self.items.previous(),
+            Action::PageDown => self.items.forward(self.page_size()),
+            Action::PageUp => self.items.backward(self.page_size()),
+            Action::HalfPageDown => self.items.forward(self.page_size() / 2),
+            Action::HalfPageUp => self.items.backward(self.page_size() / 2),
+            Action::First => self.items.first(),
+            Action::Last => match pending_jump {
+                Some(number) => self.items.select(number.saturating_sub(1)),
+                None => self.items.last(),
+            },
+            Action::JumpDigit => {
+                // Build up the entry number to jump to, one digit at a time
+                if let KeyCode::Char(c) = key.code {
+                    let digit = c.to_digit(10).unwrap_or_default() as usize;
+                    self.pending_jump = Some(
+                        pending_jump
+                            .unwrap_or(0)
+                            .saturating_mul(10)
+                            .saturating_add(digit),
+                    );
+                }
+            }
+            Action::EditSelected => {
+                if self.items.selected().is_some_and(|item| !item.is_group()) {
+                    self.editor = Some(TextArea::new(&self.selected()));
+                }
+            }
+            Action::MakeTemplate => {
+                let command = self.selected();
+                if self.items.selected().and_then(Item::template).is_some() {
+                    self.notice = Some("this is already a template".to_string());
+                } else if !command.is_empty() {
+                    self.marking = Some(Marking::new(&command));
+                }
+            }
+            Action::ToggleStar => self.toggle_star(),
+            Action::EditNote => {
+                let command = self.selected();
+                if !command.is_empty() {
+                    self.note_form = Some(NoteForm::new(self.favorite(&command), &command));
+                }
+            }
+            Action::ToggleFavorites => {
+                self.scope = match self.scope {
+                    Scope::All => Scope::Favorites,
+                    Scope::Favorites => Scope::All,
+                };
+                self.apply_filter();
+            }
+            Action::CycleSource => {
+                if self.source_labels.len() < 2 {
+                    self.notice = Some("only one history source is loaded".to_string());
+                    return None;
+                }
+                self.source_filter = match self.source_filter {
+                    None => Some(0),
+                    Some(i) if i + 1 < self.source_labels.len() => Some(i + 1),
+                    Some(_) => None,
+                };
+                self.apply_filter();
+            }
+            Action::ToggleIgnored => {
+                if self.ignored == 0 {
+                    self.notice = Some("no entries are ignored".to_string());
+                    return None;
+                }
+                self.show_ignored = !self.show_ignored;
+                self.apply_filter();
+            }
+            Action::ToggleExplain => self.show_explain = !self.show_explain,
+            Action::ToggleGroups => {
+                self.grouped = !self.grouped;
+                self.apply_filter();
+            }
+            Action::ExpandGroup | Action::CollapseGroup => {
+                if let Some((name, expanded)) = self.selected_group() {
+                    let expand = action == Action::ExpandGroup;
+                    if expand != expanded {
+                        self.expand(name, expand);
+                    }
+                }
+            }
+
+            // Search box
+            Action::InsertChar => {
+                if let KeyCode::Char(c) = key.code {
+                    self.input.insert(self.input_pos as usize, c);
+                    self.input_pos += 1;
+                }
+            }
+            Action::DeleteChar => {
+                if self.input_pos > 0 && self.input_pos - 1 < self.input.width() as u64 {
+                    self.input.remove((self.input_pos as usize) - 1);
+                    self.input_pos -= 1;
+                }
+            }
+            Action::CursorLeft => self.input_pos = self.input_pos.saturating_sub(1),
+            Action::CursorRight => {
+                self.input_pos += 1;
+                if self.input_pos > self.input.width() as u64 {
+                    self.input_pos = self.input.width() as u64;
+                }
+            }
+            // Handle Cmd+LeftArrow (interpreted as Ctrl+a)
+            Action::CursorStart => self.input_pos = 0,
+            // Handle Cmd+RightArrow (interpreted as Ctrl+e)
+            Action::CursorEnd => self.input_pos = self.input.width() as u64,
+            Action::WordLeft => {
+                // Handle Alt+LeftArrow (interpreted as Alt+b)
+
+                // Find the first position that has a space (or 0 if it gets to that)
+                match find_previous_space(&self.input, self.input_pos as usize) {
+                    Some(index) => self.input_pos = (index as u64) + 1,
+                    None => self.input_pos = 0,
+                }
+            }
+            Action::WordRight => {
+                // Handle Alt+RightArrow (interpreted as Alt+f)
+
+                // Find the first position that has a space (or the end if it gets to that)
+                match find_next_space(&self.input, self.input_pos as usize) {
+                    Some(index) => self.input_pos = (index as u64) + 1,
+                    None => self.input_pos = self.input.width() as u64,
+                }
+            }
+            Action::DeleteLine => {
+                // Handle Cmd+Backspace (interpreted as Ctrl+u)
+                self.input.drain(..);
+                self.input_pos = 0;
+            }
+            Action::DeleteWord => {
+                // Handle Alt+Backspace (interpreted as Ctrl+w)
+
+                // Find the first position that has a space (or 0 if it gets to that)
+                let idx =
+                    find_previous_space(&self.input, self.input_pos as usize).unwrap_or_default();
+
+                if idx == 0 {
+                    self.input.drain(..);
+                    self.input_pos = 0;
+                } else {
+                    self.input.drain((idx + 1)..self.input_pos as usize);
+                    self.input_pos = (idx + 1) as u64;
+                }
+            }
+            Action::FinishSearch => self.input_mode = InputMode::Normal,
+            Action::CancelSearch => {
+                // Empty the input if nothing is done.
+                self.input.drain(..);
+                self.input_pos = 0;
+                self.apply_filter();
+                self.input_mode = InputMode::Normal;
+            }
+
+            // Edit popup, see `edit_text` for the editing itself
+            Action::AcceptEdit => {
+                if let Some(editor) = self.editor.take() {
+                    return self.finish(editor.text(), false);
+                }
+            }
+            Action::CancelEdit => self.editor = None,
+            Action::CursorUp | Action::CursorDown | Action::NewLine => {}
+
+            // Templates, see above for moving between words and typing values
+            Action::TogglePlaceholder => {
+                if let Some(marking) = self.marking.as_mut() {
+                    marking.toggle();
+                }
+            }
+            Action::SaveTemplate => self.save_template(),
+            Action::CancelTemplate => self.marking = None,
+            Action::NextField => {
+                if let Some(form) = self.form.as_mut() {
+                    form.next_field();
+                }
+            }
+            Action::PreviousField => {
+                if let Some(form) = self.form.as_mut() {
+                    form.previous_field();
+                }
+            }
+            Action::NextSuggestion => {
+                if let Some(form) = self.form.as_mut() {
+                    form.next_suggestion(&self.templates[form.template]);
+                }
+            }
+            Action::AcceptForm => {
+                if let Some(form) = self.form.take() {
+                    let template = &mut self.templates[form.template];
+                    let command = template.fill(&form.values);
+                    template.remember(&form.values);
+                    // The command is still used when the suggestions can't be saved
+                    let _ = self.save_templates();
+                    return self.finish(command, self.run_form);
+                }
+            }
+            Action::CancelForm => self.form = None,
+
+            // Favorites, see above for typing the note and tags
+            Action::SaveNote => self.save_note(),
+            Action::CancelNote => self.note_form = None,
+
+            // Confirming a dangerous command
+            Action::ConfirmRun => {
+                if let Some(confirm) = self.confirm.take() {
+                    return Some(Exit::Run(confirm.command));
+                }
+            }
+            Action::CancelRun => self.confirm = None,
+
+            // Anywhere
+            Action::CycleSearchMode => {
+                self.search_mode = self.search_mode.next();
+                self.apply_filter();
+            }
+            Action::ToggleSort => {
+                self.sort_order = self.sort_order.toggle();
+                self.apply_filter();
+            }
+            Action::ShowHelp => {
+                self.show_help = true;
+                self.help_scroll = 0;
+            }
+
+            // Help overlay
+            Action::CloseHelp => self.show_help = false,
+            Action::ScrollHelpDown => self.help_scroll = self.help_scroll.saturating_add(1),
+            Action::ScrollHelpUp => self.help_scroll = self.help_scroll.saturating_sub(1),
+
+            // Statistics
+            Action::ShowStats => self.show_stats = true,
+            Action::CloseStats => self.show_stats = false,
+        }
+        None
+    }
+
+    /// Filters the full history with the current input, search mode and sort order.
+    fn apply_filter(&mut self) {
+        let started = Instant::now();
+        // Favorites are also found by their note and tags
+        let favorites: HashMap<&str, &Favorite> = self
+            .favorites
+            .iter()
+            .map(|favorite| (favorite.command.as_str(), favorite))
+            .collect();
+        let (query, narrowed) = period::split_query(&self.input, Local::now());
+        self.highlight = Highlight::new(&query, self.search_mode);
+        self.query = query;
+        self.narrowed = narrowed.and(self.period);
+        let narrowed = self.narrowed;
+        let scope = self.scope;
+        let source_filter = self.source_filter;
+        let show_ignored = self.show_ignored;
+        let history = &self.history;
+        let entries = self
+            .full_history
+            .iter()
+            .filter(|item| scope == Scope::All || favorites.contains_key(item.command.as_str()))
+            .filter(|item| show_ignored || !item.entry().is_some_and(|e| history[e].ignored))
+            .filter(|item| match source_filter {
+                Some(source) => item
+                    .entry()
+                    .is_some_and(|entry| history[entry].sources.contains(&source)),
+                None => true,
+            })
+            .filter(|item| {
+                // Templates and lost favorites were never run, in any period
+                narrowed.is_all()
+                    || item
+                        .entry()
+                        .is_some_and(|entry| narrowed.contains(history[entry].timestamp))
+            });
+        match search::filter(
+            &self.fuzzy_matcher,
+            entries,
+            |item| match favorites.get(item.command.as_str()) {
+                Some(favorite) => Cow::Owned(favorite.searchable()),
+                None => Cow::Borrowed(&item.command),
+            },
+            &self.query,
+            self.search_mode,
+            self.sort_order,
+        ) {
+            Ok(matches) => {
+                let matches = matches.into_iter().cloned().collect();
+                self.items = if self.grouped {
+                    self.matches = matches;
+                    Picker::new(picker::group(&self.matches, &self.expanded))
+                } else {
+                    self.matches = Vec::new();
+                    Picker::new(matches)
+                };
+                self.filter_error = None;
+            }
+            // Keep showing the last results while the pattern is being typed
+            Err(_) => self.filter_error = Some("invalid regex".to_string()),
+        }
+        self.filter_time = Some(started.elapsed());
+    }
+
+    fn is_loading(&self) -> bool {
+        self.loading.contains(&true)
+    }
+
+    /// Merges the history again with the commands read from its sources since
+    /// the last time, keeping the search and the selected command.
+    fn reload(&mut self) {
+        let mut changed = false;
+        for loaded in self.loader.try_iter() {
+            match loaded {
+                // The source was read again since, along with these commands
+                Loaded::Older(source, _) if !self.loading[source] => {}
+                Loaded::Older(source, mut commands) => {
+                    commands.append(&mut self.histories[source]);
+                    self.histories[source] = commands;
+                    self.sources[source].loaded = Ok(self.histories[source].len());
+                    changed = true;
+                }
+                Loaded::Done(source, read) => {
+                    if let (Err(err), true) = (read, self.loading[source]) {
+                        self.sources[source].loaded = Err(err.to_string());
+                    }
+                    self.loading[source] = false;
+                }
+            }
+        }
+        let updates = match &mut self.watch {
+            Some(watch) => watch.updates(),
+            None => Vec::new(),
+        };
+        if updates.is_empty() && !changed {
+            return;
+        }
+        for (source, update) in updates {
+            match update {
+                Update::Appended(commands) => self.histories[source].extend(commands),
+                Update::Replaced(Ok(commands)) => {
+                    self.histories[source] = commands;
+                    self.loading[source] = false;
+                }
+                // Keep what was read before, and say why it couldn't be read again
+                Update::Replaced(Err(err)) => {
+                    self.sources[source].loaded = Err(err.to_string());
+                    continue;
+                }
+            }
+            self.sources[source].loaded = Ok(self.histories[source].len());
+        }
+
+        let selected = self
+            .items
+            .selected()
+            .map(|item| (item.command.clone(), item.level));
+        self.history = history::merge(&self.histories, self.config.dedup, &self.ignore);
+        self.full_history = picker::list_items(&self.templates, &self.history, &self.favorites);
+        self.ignored = self.history.iter().filter(|entry| entry.ignored).count();
+        self.stats = None;
+        self.apply_filter();
+        if let Some(index) = self
+            .items
+            .items()
+            .iter()
+            .position(|item| selected.as_ref() == Some(&(item.command.clone(), item.level)))
+        {
+            self.items.select(index);
+        }
+    }
+
+    /// Reacts to a key press or a mouse event. Returns the message to print
+    /// on exit when the event ends the program.
+    fn handle_event(&mut self, event: Event) -> Option<Exit> {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                if let Some(action) = self.keymap.action(self.key_mode(), &key) {
+                    if let Some(exit) = self.handle_action(action, key) {
+                        return Some(exit);
+                    }
+                }
+            }
+            Event::Mouse(MouseEvent {
+                kind, column, row, ..
+            }) if !self.show_help && !self.show_stats && self.confirm.is_none() => {
+                match self.input_mode {
+                    InputMode::Normal => match kind {
+                        MouseEventKind::Down(MouseButton::Left) => {
+                            // If you've click within a chunk, check which chunk it is to see which mode to select
+                            if column >= self.chunks[1].x
+                                && column < self.chunks[1].x + self.chunks[1].width
+                                && row >= self.chunks[1].y
+                                && row < self.chunks[1].y + self.chunks[1].height
+                            {
+                                self.input = "".to_string();
+                                self.input_pos = 0;
+                                self.input_mode = InputMode::Editing;
+                            } else if let Some(index) = self.item_at(column, row) {
+                                // A second click on the same entry accepts it
+                                let double_click = matches!(
+                                    self.last_click,
+                                    Some((at, last)) if last == index && at.elapsed() < DOUBLE_CLICK
+                                );
+                                if double_click {
+                                    if let Some(exit) = self.accept(false) {
+                                        return Some(exit);
+                                    }
+                                }
+                                self.items.select(index);
+                                self.last_click = Some((Instant::now(), index));
+                            }
+                        }
+                        MouseEventKind::ScrollUp => self.items.backward(self.config.scroll_lines),
+                        MouseEventKind::ScrollDown => self.items.forward(self.config.scroll_lines),
+                        _ => {}
+                    },
+                    InputMode::Editing => {
+                        // If you've click within a chunk, check which chunk it is to see which mode to select
+                        if kind == MouseEventKind::Down(MouseButton::Left)
+                            && column >= self.chunks[0].x
+                            && column < self.chunks[0].x + self.chunks[0].width
+                            && row >= self.chunks[0].y
+                            && row < self.chunks[0].y + self.chunks[0].height
+                        {
+                            self.input_mode = InputMode::Normal;
+                            if let Some(index) = self.item_at(column, row) {
+                                self.items.select(index);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn on_tick(&mut self) {
+        self.reload();
+        if let InputMode::Editing = self.input_mode {
+            // Only change the item state if the input is being updated. If not,
+            // then no need to keep updating.
+            if self.input_prev != self.input {
+                self.apply_filter();
+            }
+            self.input_prev = self.input.to_string();
+        }
+    }
+}
+
+/// Unwraps data loaded from hui's data directory and reports it as a source
+/// called `name`. Also returns whether the data may be saved again, which it
+/// may not when the file couldn't be read, so that it isn't overwritten.
+fn saved_data<T>(
+    name: &str,
+    data: io::Result<Vec<T>>,
+    sources: &mut Vec<SourceStatus>,
+) -> (Vec<T>, bool) {
+    match data {
+        Ok(data) => {
+            if !data.is_empty() {
+                sources.push(SourceStatus {
+                    name: name.to_string(),
+                    loaded: Ok(data.len()),
+                });
+            }
+            (data, true)
+        }
+        Err(err) => {
+            sources.push(SourceStatus {
+                name: name.to_string(),
+                loaded: Err(err.to_string()),
+            });
+            (Vec::new(), false)
+        }
+    }
+}
+
+/// How loading one history file went, shown in the status bar.
+struct SourceStatus {
+    name: String,
+    // Number of entries loaded, or why loading failed.
+    loaded: Result<usize, String>,
+}
+
+/// The history sources, shared with the threads reading them.
+type Sources = Vec<Arc<dyn HistorySource>>;
+
+/// The history sources to load: those of this machine, and what was synced
+/// from others.
+fn history_sources() -> Sources {
+    local_sources()
+        .into_iter()
+        .chain(
+            sync::sources()
+                .into_iter()
+                .map(|source| Arc::new(source) as Arc<dyn HistorySource>),
+        )
+        .collect()
+}
+
+/// The history sources of this machine: those in `$HUI_SOURCES` or the shell
+/// in `$HUI_TERM`, and anything imported from other tools.
+fn local_sources() -> Sources {
+    // Determine the history files to load from HUI_SOURCES,
+    // or the single shell set in HUI_TERM.
+    let specs = match env::var("HUI_SOURCES") {
+        Ok(sources) if !sources.trim().is_empty() => Ok(sources),
+        _ => match env::var_os("HUI_TERM") {
+            Some(term) => {
+                if term == "zsh" || term == "bash" || term == "fish" {
+                    Ok(term.to_string_lossy().into_owned())
+                } else {
+                    Err("Currently only 'bash', 'zsh' or 'fish' are supported for $HUI_TERM.")
+                }
+            }
+            None => Err("$HUI_TERM or $HUI_SOURCES needs to be set."),
+        },
+    }
+    .unwrap();
+    // Anything imported from other tools is searched as well
+    specs
+        .split(',')
+        .filter(|spec| !spec.trim().is_empty())
+        .map(Source::parse)
+        .chain(import::sources())
+        .map(|source| Arc::new(source) as Arc<dyn HistorySource>)
+        .collect()
+}
+
+/// Loads every history source, returning the sources, the commands read from
+/// each of them and how loading each of them went, in the same order.
+fn load_history() -> (Sources, Vec<Vec<Command>>, Vec<SourceStatus>) {
+    load_sources(history_sources())
+}
+
+fn load_sources(sources: Sources) -> (Sources, Vec<Vec<Command>>, Vec<SourceStatus>) {
+    // Read every source and merge them into one timeline. A file that
+    // can't be read is left out and reported in the status bar.
+    let mut histories = Vec::new();
+    let mut statuses = Vec::new();
+    for source in &sources {
+        let (commands, loaded) = match source.read() {
+            Ok(commands) => {
+                let count = commands.len();
+                (commands, Ok(count))
+            }
+            Err(err) => (Vec::new(), Err(err.to_string())),
+        };
+        histories.push(commands);
+        statuses.push(SourceStatus {
+            name: source.name().to_string(),
+            loaded,
+        });
+    }
+    (sources, histories, statuses)
+}
+
+fn labels(sources: &[Arc<dyn HistorySource>]) -> Vec<String> {
+    sources
+        .iter()
+        .map(|source| source.label().to_string())
+        .collect()
+}
+
+/// Reads the ignore rules. When they can't be read nothing is ignored, and
+/// why is reported along with the history sources.
+fn load_ignore(statuses: &mut Vec<SourceStatus>) -> Ignore {
+    Ignore::load().unwrap_or_else(|err| {
+        statuses.push(SourceStatus {
+            name: "ignore rules".to_string(),
+            loaded: Err(err.to_string()),
+        });
+        Ignore::default()
+    })
+}
+
+/// Reports what couldn't be loaded on stderr, for the commands without an interface.
+fn warn_skipped(statuses: Vec<SourceStatus>) {
+    for status in statuses {
+        if let Err(err) = status.loaded {
+            eprintln!("Skipped {}: {}", status.name, err);
+        }
+    }
+}
+
+/// Imports the history of `tool` from `path`, or from where the tool keeps it
+/// by default, replacing anything imported from it before.
+fn import(tool: import::Tool, path: Option<PathBuf>) -> io::Result<String> {
+    let path = match path {
+        Some(path) => path,
+        None => tool.default_path()?,
+    };
+    let commands = tool.read(&path)?;
+    store::save(&tool.file(), &commands)?;
+    Ok(format!(
+        "Imported {} commands from {}.",
+        commands.len(),
+        path.display()
+    ))
+}
+
+/// Appends the history of this machine to its log in the sync directory and
+/// reads the logs of the others, with the arguments of `hui sync` or else
+/// those it was last run with.
+fn sync(args: sync::Settings, new_key_file: Option<PathBuf>, new_salt: bool) -> io::Result<String> {
+    let saved: sync::Settings = store::load(sync::FILE)?;
+    let settings = sync::Settings {
+        dir: args.dir.or(saved.dir),
+        host: args.host.or(saved.host),
+        encrypt: args.encrypt || saved.encrypt,
+        key_file: new_key_file.clone().or(args.key_file).or(saved.key_file),
+    };
+    let Some(dir) = &settings.dir else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no sync directory yet, pass one with --dir",
+        ));
+    };
+    // A directory that is missing is more likely an unmounted share than a
+    // new one, and creating it would sync into the void
+    if !dir.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: no such directory", dir.display()),
+        ));
+    }
+    let host = match &settings.host {
+        Some(host) => sync::host_name(host),
+        None => sync::hostname(),
+    };
+    let key = match (&settings.key_file, env::var("HUI_SYNC_PASSPHRASE")) {
+        (Some(path), _) if new_key_file.is_some() => {
+            let key = Key::generate();
+            key.write(path).map_err(|err| match err.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    err.kind(),
+                    format!(
+                        "{}: already exists, pass it with --key-file",
+                        path.display()
+                    ),
+                ),
+                _ => err,
+            })?;
+            Some(key)
+        }
+        (Some(path), _) => Some(Key::read(path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                err.kind(),
+                format!(
+                    "{}: no such key file, create one with --new-key-file",
+                    path.display()
+                ),
+            ),
+            _ => err,
+        })?),
+        (None, Ok(passphrase)) if !passphrase.is_empty() => {
+            Some(sync::passphrase_key(dir, &passphrase, new_salt)?)
+        }
+        (None, _) if new_salt => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "--new-salt needs $HUI_SYNC_PASSPHRASE to be set",
+            ))
+        }
+        (None, _) => None,
+    };
+    if settings.encrypt && key.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "encrypting needs a key, set $HUI_SYNC_PASSPHRASE or pass --key-file",
+        ));
+    }
+
+    // Only what this machine ran is shared, and nothing the ignore rules hide
+    let (_, histories, mut statuses) = load_sources(local_sources());
+    let ignore = load_ignore(&mut statuses);
+    warn_skipped(statuses);
+    let mut local: Vec<Command> = histories
+        .into_iter()
+        .flatten()
+        .filter(|command| !ignore.is_ignored(&command.command))
+        .collect();
+    local.sort_by_key(|command| command.timestamp);
+
+    let pushed = sync::push(dir, &host, &local, key.as_ref())?;
+    let mut pulled = Vec::new();
+    for (other, commands) in sync::pull(dir, &host, &local, key.as_ref())? {
+        match commands {
+            Ok(commands) => pulled.push((other, commands)),
+            Err(err) => eprintln!("Skipped the log of {}: {}", other, err),
+        }
+    }
+    sync::save(&pulled)?;
+    store::save(sync::FILE, &settings)?;
+
+    let count: usize = pulled.iter().map(|(_, commands)| commands.len()).sum();
+    let hosts: Vec<&str> = pulled.iter().map(|(host, _)| host.as_str()).collect();
+    let mut message = format!("Appended {} new commands to the log of {}", pushed, host);
+    if !hosts.is_empty() {
+        message += &format!("; {} commands from {}", count, hosts.join(", "));
+    }
+    Ok(message + ".")
+}
+
+/// Asks for the passphrase of the data files on the terminal, without
+/// showing what is typed. Returns `None` when there is no terminal to ask on.
+fn ask_passphrase() -> io::Result<Option<String>> {
+    if enable_raw_mode().is_err() {
+        return Ok(None);
+    }
+    eprint!("Passphrase for hui's data: ");
+    let mut passphrase = String::new();
+    let read = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"))
+                }
+                KeyCode::Char(c) => passphrase.push(c),
+                KeyCode::Backspace => {
+                    passphrase.pop();
+                }
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(err) => break Err(err),
+        }
+    };
+    disable_raw_mode()?;
+    eprintln!();
+    read.map(|()| Some(passphrase))
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let config = Config::from_env();
+    let period = args.period();
+    // `hui key rotate` only takes the key from the environment
+    if !matches!(args.command, Some(Commands::Key { .. })) && store::needs_passphrase() {
+        if let Some(passphrase) = ask_passphrase()? {
+            store::use_passphrase(passphrase);
+        }
+    }
+    match args.command {
+        Some(Commands::Import { tool, path }) => {
+            match import(tool, path) {
+                Ok(message) => println!("{}", message),
+                Err(err) => {
+                    eprintln!("Couldn't import the {} history: {}", tool.name(), err);
+                    process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Some(Commands::Sync {
+            dir,
+            host,
+            encrypt,
+            key_file,
+            new_key_file,
+            new_salt,
+        }) => {
+            let settings = sync::Settings {
+                dir,
+                host,
+                encrypt,
+                key_file,
+            };
+            match sync(settings, new_key_file, new_salt) {
+                Ok(message) => println!("{}", message),
+                Err(err) => {
+                    eprintln!("Couldn't sync: {}", err);
+                    process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Some(Commands::Key {
+            command: KeyCommand::Rotate,
+        }) => {
+            let passphrase = env::var("HUI_NEW_PASSPHRASE").ok();
+            let passphrase = passphrase.as_deref().filter(|p| !p.is_empty());
+            match store::rotate(passphrase) {
+                Ok(count) => {
+                    println!("Encrypted {} data files with a new key.", count);
+                    if passphrase.is_some() {
+                        println!("Set $HUI_PASSPHRASE to the new passphrase from now on.");
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Couldn't rotate the key: {}", err);
+                    process::exit(1);
+                }
+            }
+            return Ok(());
         }
-    }
-
-    fn on_tick(&mut self) {
-        match self.input_mode {
-            InputMode::Editing => {
-                // Only change the item state if the input is being updated. If not,
-                // then no need to keep updating.
-                if self.input_prev != self.input {
-                    // Fuzzy search the full history and sort by relevance
-                    let full_history = self.full_history.to_vec();
-                    let mut matches: Vec<_> = full_history
-                        .iter()
-                        .filter_map(|s| {
-                            self.fuzzy_matcher
-                                .fuzzy_match(s, &self.input)
-                                .map(|score| (score, s))
-                        })
-                        .collect();
-
-                    // Sort by match score in descending order
-                    matches.sort_by(|(score_a, _), (score_b, _)| score_b.cmp(score_a));
-                    let sorted_matches: Vec<_> =
-                        matches.into_iter().map(|(_, s)| s.clone()).collect();
-
-                    self.items = StatefulList::with_items(sorted_matches);
+        Some(Commands::Search(mut search)) => {
+            search.favorites = args.favorites;
+            search.period = period;
+            let (sources, histories, mut statuses) = load_history();
+            let labels = labels(&sources);
+            let ignore = load_ignore(&mut statuses);
+            warn_skipped(statuses);
+            let history = history::merge(&histories, config.dedup, &ignore);
+            let history: Vec<Entry> = history.into_iter().filter(|e| !e.ignored).collect();
+            let favorites: Vec<Favorite> = store::load(favorites::FILE).unwrap_or_default();
+            let mut stdout = io::stdout().lock();
+            match query::run(&search, &history, &labels, &favorites, &mut stdout) {
+                Ok(0) => process::exit(1),
+                Ok(_) => return Ok(()),
+                Err(err) => {
+                    eprintln!("hui: {}", err);
+                    process::exit(2);
                 }
-                self.input_prev = self.input.to_string();
             }
-            _ => {}
         }
-    }
-}
-
-fn main() -> Result<(), Box<dyn Error>> {
-    // Determine the history file to fetch based on
-    // the HUI_TERM environment variable.
-    let history_file = match env::var_os("HUI_TERM") {
-        Some(term) => {
-            if term == "zsh" {
-                Ok(".zsh_history".to_string())
-            } else if term == "bash" {
-                Ok(".bash_history".to_string())
+        Some(Commands::Export { format }) => {
+            // Everything is exported, including what the ignore rules hide
+            let (sources, histories, statuses) = load_history();
+            let labels = labels(&sources);
+            warn_skipped(statuses);
+            let history = history::merge(&histories, config.dedup, &Ignore::default());
+            let history: Vec<Entry> = history
+                .into_iter()
+                .filter(|entry| period.contains(entry.timestamp))
+                .collect();
+            export::write(format, &history, &labels, &mut io::stdout().lock())?;
+            return Ok(());
+        }
+        Some(Commands::Stats { json }) => {
+            let (_, mut histories, mut statuses) = load_history();
+            let ignore = load_ignore(&mut statuses);
+            warn_skipped(statuses);
+            for commands in &mut histories {
+                commands.retain(|command| period.contains(command.timestamp));
+            }
+            let stats = Stats::new(&histories, &ignore, Local::now());
+            let mut stdout = io::stdout().lock();
+            if json {
+                stats.write_json(&mut stdout)?;
             } else {
-                Err("Currently only 'bash' or 'zsh' are supported for $HUI_TERM.")
+                stats.write_text(&mut stdout)?;
             }
+            return Ok(());
         }
-        None => Err("$HUI_TERM needs to be set."),
+        None => {}
     }
-    .unwrap();
 
-    // Fetch the history based on the HUI_TERM environment
-    // variable that is set.
-    let history = history::fetch(history_file);
+    // The history is read while the interface is already up, most recent
+    // commands first, so that even very large files don't keep it waiting
+    let sources = history_sources();
+    let labels = labels(&sources);
+    let mut statuses: Vec<SourceStatus> = sources
+        .iter()
+        .map(|source| SourceStatus {
+            name: source.name().to_string(),
+            loaded: Ok(0),
+        })
+        .collect();
+    let ignore = load_ignore(&mut statuses);
+    let danger = Danger::load().unwrap_or_else(|err| {
+        statuses.push(SourceStatus {
+            name: "danger list".to_string(),
+            loaded: Err(err.to_string()),
+        });
+        Danger::default()
+    });
+    let (loader, lengths) = load::start(&sources);
+    let watch = match Watch::new(sources, lengths) {
+        Ok(watch) => Some(watch),
+        Err(err) => {
+            statuses.push(SourceStatus {
+                name: "live reload".to_string(),
+                loaded: Err(err.to_string()),
+            });
+            None
+        }
+    };
+
+    let clipboard = ClipboardContext::new().map_err(|err| err.to_string())?;
 
-    // setup terminal
+    // setup terminal. The UI is drawn on stderr so that stdout
+    // only carries the accepted command when HUI_OUTPUT=stdout.
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    let mut stderr = io::stderr();
+    execute!(stderr, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stderr);
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
     let tick_rate = Duration::from_millis(250);
-    let app = App::new(history);
-    let res = run_app(&mut terminal, app, tick_rate);
+    let templates = store::load(template::FILE);
+    let favorites = store::load(favorites::FILE);
+    let output = config.output;
+    let mut app = App::new(
+        ignore,
+        danger,
+        labels,
+        templates,
+        favorites,
+        statuses,
+        config,
+        loader,
+        watch,
+        Box::new(clipboard),
+    );
+    app.period = period;
+    if args.favorites {
+        app.scope = Scope::Favorites;
+    }
+    if args.favorites || !app.period.is_all() {
+        app.apply_filter();
+    }
+    let res = run_app(&mut terminal, app, tick_rate, &mut TerminalEvents);
 
     // restore terminal
     disable_raw_mode()?;
@@ -203,16 +1549,73 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     match res {
         Err(err) => println!("{:?}", err),
-        Ok(resp) => {
-            if resp != "" {
+        Ok(Exit::Print(resp)) => {
+            if !resp.is_empty() {
                 println!("{}", resp);
             }
         }
+        Ok(Exit::Run(command)) => return run(&command, output),
     }
 
     Ok(())
 }
 
+/// Runs an accepted command once the terminal is restored. Shell
+/// integrations are told to run it by the exit status, so that it ends up in
+/// the shell's history; otherwise hui is replaced by `$SHELL -c` running it.
+fn run(command: &str, output: Output) -> Result<(), Box<dyn Error>> {
+    match output {
+        Output::Stdout => {
+            println!("{}", command);
+            io::stdout().flush()?;
+            process::exit(RUN_STATUS);
+        }
+        Output::Clipboard => {
+            let shell = env::var_os("SHELL")
+                .filter(|shell| !shell.is_empty())
+                .unwrap_or_else(|| "sh".into());
+            // Show what runs, since it doesn't show up at the prompt
+            eprintln!("{}", command);
+            let mut shell = process::Command::new(shell);
+            shell.arg("-c").arg(command);
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::CommandExt;
+                // Only returns when the shell couldn't be started
+                Err(shell.exec().into())
+            }
+            #[cfg(not(unix))]
+            {
+                process::exit(shell.status()?.code().unwrap_or(1));
+            }
+        }
+    }
+}
+
+/// Applies a text editing action to the edit popup. Returns whether the action was one.
+fn edit_text(editor: &mut TextArea, action: Action, key: KeyEvent) -> bool {
+    match action {
+        Action::InsertChar => {
+            if let KeyCode::Char(c) = key.code {
+                editor.insert(c);
+            }
+        }
+        Action::NewLine => editor.newline(),
+        Action::DeleteChar => editor.backspace(),
+        Action::DeleteWord => editor.delete_word(),
+        Action::CursorLeft => editor.left(),
+        Action::CursorRight => editor.right(),
+        Action::CursorUp => editor.up(),
+        Action::CursorDown => editor.down(),
+        Action::CursorStart => editor.home(),
+        Action::CursorEnd => editor.end(),
+        Action::WordLeft => editor.word_left(),
+        Action::WordRight => editor.word_right(),
+        _ => return false,
+    }
+    true
+}
+
 fn find_previous_space(string: &str, start_index: usize) -> Option<usize> {
     string.char_indices().rev().find_map(|(idx, ch)| {
         // idx + 1 != start_index - ignores the immediate next position so it can jump full words.
@@ -238,237 +1641,24 @@ fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
     tick_rate: Duration,
-) -> io::Result<String> {
+    events: &mut impl EventSource,
+) -> io::Result<Exit> {
     let mut last_tick = Instant::now();
     loop {
         terminal.draw(|f| ui(f, &mut app))?;
 
+        // Show what has been read of the history as soon as it is
+        let tick_rate = if app.is_loading() {
+            LOADING_TICK.min(tick_rate)
+        } else {
+            tick_rate
+        };
         let timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
-        let event = if crossterm::event::poll(timeout)? {
-            // Read the event
-            Some(event::read()?)
-        } else {
-            None
-        };
-        match app.input_mode {
-            InputMode::Normal => {
-                if let Some(Event::Mouse(MouseEvent {
-                    kind, column, row, ..
-                })) = event
-                {
-                    match kind {
-                        MouseEventKind::Down(MouseButton::Left) => {
-                            // If you've click within a chunk, check which chunk it is to see which mode to select
-                            if column >= app.chunks[1].x
-                                && column < app.chunks[1].x + app.chunks[1].width
-                                && row >= app.chunks[1].y
-                                && row < app.chunks[1].y + app.chunks[1].height
-                            {
-                                app.input = "".to_string();
-                                app.input_pos = 0;
-                                app.input_mode = InputMode::Editing;
-                            }
-                        }
-                        MouseEventKind::ScrollUp => app.items.previous(),
-                        MouseEventKind::ScrollDown => app.items.next(),
-                        _ => {}
-                    }
-                } else if let Some(Event::Key(key)) = event {
-                    if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Char('/') => {
-                                app.input = "".to_string();
-                                app.input_pos = 0;
-                                app.input_mode = InputMode::Editing;
-                            }
-                            KeyCode::Char('q') => {
-                                return Ok("".to_string());
-                            }
-                            KeyCode::Down => app.items.next(),
-                            KeyCode::Up => app.items.previous(),
-                            KeyCode::Enter => {
-                                let index = app.items.selected_index();
-                                let val = match app.items.items.get(index) {
-                                    Some(val) => val.to_string(),
-                                    None => "".to_string(),
-                                };
-                                // Copy the text to the clipboard before quitting
-                                app.clipboard.set_contents(val.clone()).unwrap();
-                                return Ok(format!("Copied to clipboard: {}", val.to_string()));
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-            InputMode::Editing => {
-                if let Some(Event::Mouse(MouseEvent {
-                    kind, column, row, ..
-                })) = event
-                {
-                    match kind {
-                        MouseEventKind::Down(MouseButton::Left) => {
-                            // If you've click within a chunk, check which chunk it is to see which mode to select
-                            if column >= app.chunks[0].x
-                                && column < app.chunks[0].x + app.chunks[0].width
-                                && row >= app.chunks[0].y
-                                && row < app.chunks[0].y + app.chunks[0].height
-                            {
-                                app.input_mode = InputMode::Normal;
-                            }
-                        }
-                        _ => {}
-                    }
-                } else if let Some(Event::Key(key)) = event {
-                    if key.kind == KeyEventKind::Press {
-                        match key {
-                            // Metacharacter examples: Opt + Arrows to jump by word
-                            //                         Opt + Backspace to delete by word
-                            //                         Cmd + Arrows to jump to beginning and end
-                            //                         Cmd + Backspace to delete everything
-
-                            // Meta arrow keys
-                            KeyEvent {
-                                code: KeyCode::Char('a'),
-                                modifiers: KeyModifiers::CONTROL,
-                                ..
-                            } => {
-                                // Handle Cmd+LeftArrow (interpreted as Ctrl+a)
-                                app.input_pos = 0;
-                            }
-                            KeyEvent {
-                                code: KeyCode::Char('e'),
-                                modifiers: KeyModifiers::CONTROL,
-                                ..
-                            } => {
-                                // Handle Cmd+RightArrow (interpreted as Ctrl+e)
-                                app.input_pos = app.input.width() as u64;
-                            }
-                            KeyEvent {
-                                code: KeyCode::Char('b'),
-                                modifiers: KeyModifiers::ALT,
-                                ..
-                            } => {
-                                // Handle Alt+LeftArrow (interpreted as Ctrl+b)
-
-                                // Find the first position that has a space (or 0 if it gets to that)
-                                match find_previous_space(&app.input, app.input_pos as usize) {
-                                    Some(index) => app.input_pos = (index as u64) + 1,
-                                    None => app.input_pos = 0,
-                                }
-                            }
-                            KeyEvent {
-                                code: KeyCode::Char('f'),
-                                modifiers: KeyModifiers::ALT,
-                                ..
-                            } => {
-                                // Handle Alt+RightArrow (interpreted as Alt+f)
-
-                                // Find the first position that has a space (or the end if it gets to that)
-                                match find_next_space(&app.input, app.input_pos as usize) {
-                                    Some(index) => app.input_pos = (index as u64) + 1,
-                                    None => app.input_pos = app.input.width() as u64,
-                                }
-                            }
-
-                            // Meta Backspaces
-                            KeyEvent {
-                                code: KeyCode::Char('u'),
-                                modifiers: KeyModifiers::CONTROL,
-                                ..
-                            } => {
-                                // Handle Cmd+Backspace (interpreted as Ctrl+u)
-                                app.input.drain(..);
-                                app.input_pos = 0;
-                            }
-                            KeyEvent {
-                                code: KeyCode::Char('w'),
-                                modifiers: KeyModifiers::CONTROL,
-                                ..
-                            } => {
-                                // Handle Alt+Backspace (interpreted as Ctrl+w)
-                                // @TODO/fix This seems wrong? It should be KeyModifiers::ALT but that doesn't work for some reason...
-                                //           This will probably come back to haunt me.
-
-                                // Find the first position that has a space (or 0 if it gets to that)
-                                let idx =
-                                    match find_previous_space(&app.input, app.input_pos as usize) {
-                                        Some(index) => index,
-                                        None => 0,
-                                    };
-
-                                if idx == 0 {
-                                    app.input.drain(..);
-                                    app.input_pos = 0;
-                                } else {
-                                    app.input.drain((idx + 1)..app.input_pos as usize);
-                                    app.input_pos = (idx + 1) as u64;
-                                }
-                            }
-
-                            // Back to regular single key detections
-                            KeyEvent {
-                                code: KeyCode::Enter,
-                                ..
-                            }
-                            | KeyEvent {
-                                code: KeyCode::Up, ..
-                            }
-                            | KeyEvent {
-                                code: KeyCode::Down,
-                                ..
-                            } => {
-                                app.input_mode = InputMode::Normal;
-                            }
-                            KeyEvent {
-                                code: KeyCode::Left,
-                                ..
-                            } => {
-                                if app.input_pos > 0 {
-                                    app.input_pos -= 1;
-                                }
-                            }
-                            KeyEvent {
-                                code: KeyCode::Right,
-                                ..
-                            } => {
-                                app.input_pos += 1;
-                                if app.input_pos > app.input.width() as u64 {
-                                    app.input_pos = app.input.width() as u64;
-                                }
-                            }
-                            KeyEvent {
-                                code: KeyCode::Char(c),
-                                ..
-                            } => {
-                                app.input.insert(app.input_pos as usize, c);
-                                app.input_pos += 1;
-                            }
-                            KeyEvent {
-                                code: KeyCode::Backspace,
-                                ..
-                            } => {
-                                if app.input_pos > 0 && app.input_pos - 1 < app.input.width() as u64
-                                {
-                                    app.input.remove((app.input_pos as usize) - 1);
-                                    app.input_pos -= 1;
-                                }
-                            }
-                            KeyEvent {
-                                code: KeyCode::Esc, ..
-                            } => {
-                                // Empty the input if nothing is done.
-                                app.input.drain(..);
-                                app.input_pos = 0;
-                                app.items = StatefulList::with_items(app.full_history.to_vec());
-                                app.input_mode = InputMode::Normal;
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+        if let Some(event) = events.next(timeout)? {
+            if let Some(exit) = app.handle_event(event) {
+                return Ok(exit);
             }
         }
         if last_tick.elapsed() >= tick_rate {
@@ -488,31 +1678,127 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                 Constraint::Min(1),
                 Constraint::Length(3),
                 Constraint::Length(1),
+                Constraint::Length(1),
             ]
             .as_ref(),
         )
         .split(f.size());
+    // The explain panel takes the bottom of the list's space
+    let (chunks, explain_area) = if app.show_explain {
+        let halves = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+            .split(chunks[0]);
+        let chunks: Rc<[Rect]> = Rc::from([halves[0], chunks[1], chunks[2], chunks[3]]);
+        (chunks, Some(halves[1]))
+    } else {
+        (chunks, None)
+    };
 
+    let bold = Style::default().add_modifier(Modifier::BOLD);
     let (msg, style) = match app.input_mode {
-        InputMode::Normal => (
+        _ if app.confirm.is_some() => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("y", bold),
+                Span::raw(" to run the command anyway, "),
+                Span::styled("n", bold),
+                Span::raw(" or "),
+                Span::styled("Esc", bold),
+                Span::raw(" to go back."),
+            ],
+            Style::default(),
+        ),
+        _ if app.form.is_some() => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Tab", bold),
+                Span::raw(" for values used before, "),
+                Span::styled("Up/Down", bold),
+                Span::raw(" to switch placeholder, "),
+                Span::styled("Enter", bold),
+                Span::raw(" to accept, "),
+                Span::styled("Esc", bold),
+                Span::raw(" to cancel."),
+            ],
+            Style::default(),
+        ),
+        _ if app.note_form.is_some() => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Tab", bold),
+                Span::raw(" to switch between the note and tags, "),
+                Span::styled("Enter", bold),
+                Span::raw(" to save, "),
+                Span::styled("Esc", bold),
+                Span::raw(" to cancel."),
+            ],
+            Style::default(),
+        ),
+        _ if app.marking.is_some() => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Left/Right", bold),
+                Span::raw(" to pick a word, "),
+                Span::styled("Space", bold),
+                Span::raw(" to mark it as a placeholder, "),
+                Span::styled("Enter", bold),
+                Span::raw(" to save, "),
+                Span::styled("Esc", bold),
+                Span::raw(" to cancel."),
+            ],
+            Style::default(),
+        ),
+        _ if app.editor.is_some() => (
             vec![
                 Span::raw("Press "),
-                Span::styled("/", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(" to filter results, "),
                 Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(" to copy selected command and exit, "),
-                Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(" to exit without copying."),
+                Span::raw(" to accept the edited command, "),
+                Span::styled("Alt-Enter", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" for a new line, "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to discard changes."),
             ],
-            Style::default().add_modifier(Modifier::RAPID_BLINK),
+            Style::default(),
         ),
+        InputMode::Normal => match app.pending_jump {
+            Some(number) => (
+                vec![
+                    Span::raw("Jump to entry "),
+                    Span::styled(
+                        number.to_string(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(": press "),
+                    Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(" to jump, any other key to cancel."),
+                ],
+                Style::default(),
+            ),
+            None => (
+                vec![
+                    Span::raw("Press "),
+                    Span::styled("/", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(" to filter results, "),
+                    Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(" to copy selected command and exit, "),
+                    Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(" to exit, "),
+                    Span::styled("?", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(" for help."),
+                ],
+                Style::default().add_modifier(Modifier::RAPID_BLINK),
+            ),
+        },
         InputMode::Editing => (
             vec![
                 Span::raw("Press "),
                 Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
                 Span::raw(" to filter history, "),
                 Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(" to stop filtering."),
+                Span::raw(" to stop filtering, "),
+                Span::styled("F1", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" for help."),
             ],
             Style::default(),
         ),
@@ -520,7 +1806,8 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let mut text = Text::from(Spans::from(msg));
     text.patch_style(style);
     let help_message = Paragraph::new(text);
-    f.render_widget(help_message, chunks[2]);
+    f.render_widget(help_message, chunks[3]);
+    f.render_widget(status_bar(app), chunks[2]);
 
     let select_color = Color::Red;
 
@@ -547,12 +1834,134 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         }
     }
 
-    // Iterate through all elements in the `items` app and append some debug text to it.
+    // Number every entry so it can be jumped to by typing its number,
+    // tell templates apart from the history and star favorites.
+    let number_width = app.items.items().len().to_string().len();
+    let selected = app.items.selected_index();
+    let favorites: HashMap<&str, &Favorite> = app
+        .favorites
+        .iter()
+        .map(|favorite| (favorite.command.as_str(), favorite))
+        .collect();
+    // Only the entries that fit are drawn, so drawing takes as long however
+    // long the history is
+    let height = chunks[0].height.saturating_sub(2) as usize;
+    app.items
+        .update_offset(height, |item| item_height(&item.command));
+    let offset = app.items.offset();
     let items: Vec<ListItem> = app
         .items
-        .items
+        .items()
         .iter()
-        .map(|i| ListItem::new(i.to_string()).style(Style::default()))
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(n, item)| {
+            let number = Span::raw(format!("{:>width$} ", n + 1, width = number_width));
+            // A group shows what its entries run and how many there are
+            if let Level::Group { size, expanded } = item.level {
+                let mut spans = vec![number, Span::raw(if expanded { "▾ " } else { "▸ " })];
+                for (text, style) in app.theme.highlight(&item.command, &[]) {
+                    spans.push(Span::styled(text, style));
+                }
+                spans.push(Span::styled(
+                    format!("  ({})", format_count(size)),
+                    Style::default().fg(Color::DarkGray),
+                ));
+                return ListItem::new(Spans::from(spans));
+            }
+            let favorite = favorites.get(item.command.as_str());
+            let entry = item.entry().map(|entry| &app.history[entry]);
+            // Revealed ignored entries are dimmed rather than highlighted
+            let ignored = entry.is_some_and(|entry| entry.ignored);
+            let mut text = if ignored {
+                Text::from(item.command.as_str())
+            } else {
+                let matches = app.highlight.ranges(&app.fuzzy_matcher, &item.command);
+                Text::from(styled_lines(app.theme.highlight(&item.command, &matches)))
+            };
+            if let Some(first) = text.lines.first_mut() {
+                // Count the runs left out as duplicates
+                if let Some(entry) = entry.filter(|entry| entry.runs > 1) {
+                    first.0.push(Span::styled(
+                        format!("  ×{}", entry.runs),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                if let Some(favorite) = favorite.filter(|f| !f.annotation().is_empty()) {
+                    first.0.push(Span::styled(
+                        format!("  # {}", favorite.annotation()),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                // Show where and how the selected command last ran, when that is known
+                if let Some(entry) = entry.filter(|_| n == selected) {
+                    if !entry.context.is_empty() {
+                        first.0.push(Span::styled(
+                            format!("  ({})", entry.context.describe()),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                }
+                // Warn about commands on the danger list before they are run
+                if app.danger.is_dangerous(&item.command) {
+                    first
+                        .0
+                        .insert(0, Span::styled("⚠ ", Style::default().fg(Color::Red)));
+                }
+                // Tell sources apart when there are several of them
+                if let Some(entry) = entry.filter(|_| app.source_labels.len() > 1) {
+                    let labels: Vec<&str> = entry
+                        .sources
+                        .iter()
+                        .map(|source| app.source_labels[*source].as_str())
+                        .collect();
+                    first.0.insert(
+                        0,
+                        Span::styled(
+                            format!("[{}] ", labels.join(",")),
+                            Style::default().fg(Color::Magenta),
+                        ),
+                    );
+                }
+                if favorite.is_some() {
+                    first
+                        .0
+                        .insert(0, Span::styled("★ ", Style::default().fg(Color::Yellow)));
+                }
+                // Every run is listed, so tell them apart by when they ran
+                if let Some(time) = entry
+                    .filter(|_| app.config.dedup == Dedup::Off)
+                    .and_then(|entry| entry.timestamp)
+                    .and_then(|time| Local.timestamp_opt(time as i64, 0).single())
+                {
+                    first.0.insert(
+                        0,
+                        Span::styled(
+                            time.format("%Y-%m-%d %H:%M ").to_string(),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    );
+                }
+                if item.template().is_some() {
+                    first.0.insert(
+                        0,
+                        Span::styled("[template] ", Style::default().fg(Color::Cyan)),
+                    );
+                }
+                // Entries of an expanded group are indented under it
+                if item.level == Level::Member {
+                    first.0.insert(0, Span::raw("  "));
+                }
+                first.0.insert(0, number);
+            }
+            let style = if ignored {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            ListItem::new(text).style(style)
+        })
         .collect();
 
     // Display the version in the UI
@@ -572,161 +1981,653 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             InputMode::Editing => "  ",
         });
 
-    // We can now render the item list
-    f.render_stateful_widget(items, chunks[0], &mut app.items.state);
+    // We can now render the item list, from where it scrolled to
+    let mut state = ListState::default();
+    state.select(Some(app.items.selected_index().saturating_sub(offset)));
+    f.render_stateful_widget(items, chunks[0], &mut state);
     app.chunks = Rc::clone(&chunks);
+    if let Some(area) = explain_area {
+        render_explain(f, area, app);
+    }
+
+    if let Some(editor) = &app.editor {
+        render_editor(f, editor);
+    }
+    if let Some(marking) = &app.marking {
+        render_marking(f, marking);
+    }
+    if let Some(form) = &app.form {
+        render_form(f, form, &app.templates[form.template]);
+    }
+    if let Some(form) = &app.note_form {
+        render_note_form(f, form);
+    }
+    if let Some(confirm) = &app.confirm {
+        render_confirm(f, confirm);
+    }
+    if app.show_stats {
+        let stats = app
+            .stats
+            .get_or_insert_with(|| Stats::new(&app.histories, &app.ignore, Local::now()));
+        render_stats(f, stats);
+    }
+    if app.show_help {
+        render_help(f, app);
+    }
+}
+
+/// Draws what the programs and flags of the selected command do, as their
+/// man pages or tldr pages say, under the command itself.
+fn render_explain<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let command = app.selected();
+    let dim = Style::default().fg(Color::DarkGray);
+    let mut lines = styled_lines(app.theme.highlight(&command, &[]));
+    match app.explainer.explain(&command) {
+        _ if command.is_empty() => {}
+        None => lines.push(Spans::from(Span::styled("Reading man pages…", dim))),
+        Some(parts) => {
+            for part in parts {
+                lines.push(Spans::default());
+                let mut heading = vec![Span::styled(
+                    part.program.clone(),
+                    app.theme.style(Kind::Command),
+                )];
+                match &part.summary {
+                    Some(summary) => heading.push(Span::raw(format!(" — {}", summary))),
+                    None if !part.found => {
+                        heading.push(Span::styled(" — no man page or tldr page found", dim))
+                    }
+                    None => {}
+                }
+                lines.push(Spans::from(heading));
+                for flag in &part.flags {
+                    let text = match &flag.text {
+                        Some(text) => Span::raw(format!("  {}", first_sentence(text))),
+                        None if part.found => Span::styled("  not on its page", dim),
+                        None => Span::raw(""),
+                    };
+                    lines.push(Spans::from(vec![
+                        Span::raw("  "),
+                        Span::styled(flag.flag.clone(), app.theme.style(Kind::Flag)),
+                        text,
+                    ]));
+                }
+            }
+        }
+    }
+    let explain = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(" explain "));
+    f.render_widget(explain, area);
+}
+
+/// The start of what a man page says about a flag, which is often several
+/// paragraphs long, up to the end of its first sentence.
+fn first_sentence(text: &str) -> Cow<'_, str> {
+    let end = text
+        .match_indices(". ")
+        .find(|(at, _)| text[at + 2..].starts_with(|c: char| c.is_uppercase()));
+    match end {
+        Some((at, _)) => Cow::Owned(format!("{} …", &text[..at + 1])),
+        None => Cow::Borrowed(text),
+    }
 }
 
-// This uses a lot of what hstr-rs did to parse ZSH history:
-// https://github.com/overclockworked64/hstr-rs/blob/master/src/hstr.rs
-mod history {
-    use itertools::Itertools;
-    use regex::Regex;
-    use std::env;
-    use std::fs;
-    use std::path::Path;
-    use std::process;
+/// Draws the edit popup over the list, scrolled so that the cursor stays visible.
+fn render_editor<B: Backend>(f: &mut Frame<B>, editor: &TextArea) {
+    let area = centered_rect(80, 50, f.size());
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let (row, column) = editor.cursor();
+    let scroll_y = (row + 1).saturating_sub(inner_height);
+    let scroll_x = (column + 1).saturating_sub(inner_width);
 
-    pub type History = Vec<String>;
+    let lines: Vec<Spans> = editor
+        .lines()
+        .iter()
+        .map(|line| Spans::from(line.as_str()))
+        .collect();
+    let text = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" edit command "),
+        )
+        .scroll((scroll_y as u16, scroll_x as u16));
+    f.render_widget(Clear, area);
+    f.render_widget(text, area);
+    f.set_cursor(
+        area.x + 1 + (column - scroll_x) as u16,
+        area.y + 1 + (row - scroll_y) as u16,
+    );
+}
+
+/// Draws the help overlay over the middle of the screen, listing every
+/// binding of the keymap by mode.
+fn render_help<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let area = centered_rect(80, 80, f.size());
+    let key_width = Mode::ALL
+        .iter()
+        .flat_map(|mode| app.keymap.bindings(*mode))
+        .map(|binding| keys_label(binding).width())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for mode in Mode::ALL {
+        if !lines.is_empty() {
+            lines.push(Spans::from(""));
+        }
+        lines.push(Spans::from(Span::styled(
+            mode.name(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        for binding in app.keymap.bindings(mode) {
+            lines.push(Spans::from(vec![
+                Span::styled(
+                    format!("  {:<width$}  ", keys_label(binding), width = key_width),
+                    Style::default().fg(Color::Red),
+                ),
+                Span::raw(binding.action.description()),
+            ]));
+        }
+    }
+
+    // Don't let the help scroll past its last line
+    let max_scroll = (lines.len() as u16).saturating_sub(area.height.saturating_sub(2));
+    app.help_scroll = app.help_scroll.min(max_scroll);
+
+    let help = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(" keys "))
+        .scroll((app.help_scroll, 0));
+    f.render_widget(Clear, area);
+    f.render_widget(help, area);
+}
 
-    trait FromBytes {
-        fn from_bytes(bytes: Vec<u8>, history_type: String) -> History;
+/// Draws the statistics view over the whole list: top commands, when
+/// commands are run, and how long they take and how often they fail.
+fn render_stats<B: Backend>(f: &mut Frame<B>, stats: &Stats) {
+    let area = centered_rect(96, 90, f.size());
+    f.render_widget(Clear, area);
+    let mut title = format!(
+        " statistics: {} commands run, {} unique ",
+        format_count(stats.runs),
+        format_count(stats.unique)
+    );
+    if stats.ignored > 0 {
+        title.push_str(&format!("({} more ignored) ", format_count(stats.ignored)));
     }
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
 
-    impl FromBytes for History {
-        fn from_bytes(bytes: Vec<u8>, history_type: String) -> History {
-            // Split by ": " since that is what is a new line command for ZSH
-            // As far as I can tell, Bash automatically makes multiline commands
-            // into one line when writing to the .bash_history file? I'd need to
-            // look more into that to be sure though.
-            let s = std::str::from_utf8(&bytes).unwrap();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Min(0),
+        ])
+        .split(inner);
 
-            let pattern: &str;
-            if history_type == "zsh" {
-                pattern = std::str::from_utf8(&[10, 58, 32]).unwrap();
-            } else {
-                pattern = std::str::from_utf8(&[10]).unwrap();
-            }
-            s.split(pattern) // split on newline for bash and on "\n: " for zsh
-                .map(|line| String::from_utf8(line.as_bytes().to_vec()).unwrap())
-                .collect()
+    // Top lists, side by side
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3); 3])
+        .split(rows[0]);
+    for (i, (title, counts)) in [
+        (" top commands ", &stats.top_commands),
+        (" top binaries ", &stats.top_binaries),
+        (" top subcommands ", &stats.top_subcommands),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let lines: Vec<Spans> = counts
+            .iter()
+            .map(|count| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{:>6} ", format_count(count.count)),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::raw(stats::one_line(&count.name)),
+                ])
+            })
+            .collect();
+        let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, top[i]);
+    }
+
+    // When commands are run
+    let times = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(33)])
+        .split(rows[1]);
+    // Each hour gets an equal share of the chart, and a label when it is wide enough for one
+    let share = (times[0].width.saturating_sub(2) + 1) / 24;
+    let (bar_width, bar_gap) = if share >= 3 { (share - 1, 1) } else { (1, 0) };
+    let labels: Vec<String> = (0..24)
+        .map(|hour| match bar_width {
+            1 => String::new(),
+            _ => format!("{:02}", hour),
+        })
+        .collect();
+    let hours: Vec<(&str, u64)> = labels
+        .iter()
+        .zip(stats.hours)
+        .map(|(label, count)| (label.as_str(), count as u64))
+        .collect();
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" runs per hour "),
+        )
+        .data(&hours)
+        .bar_width(bar_width)
+        .bar_gap(bar_gap)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+    f.render_widget(chart, times[0]);
+
+    let max = stats.heatmap.iter().flatten().max().copied().unwrap_or(0);
+    let mut heatmap = vec![Spans::from(Span::styled(
+        "     0     6     12    18",
+        Style::default().fg(Color::DarkGray),
+    ))];
+    for (day, hours) in stats::WEEKDAYS.iter().zip(&stats.heatmap) {
+        let cells: String = hours
+            .iter()
+            .map(|count| stats::shade(*count, max))
+            .collect();
+        heatmap.push(Spans::from(vec![
+            Span::raw(format!("{}  ", day)),
+            Span::styled(cells, Style::default().fg(Color::Cyan)),
+        ]));
+    }
+    let heatmap = Paragraph::new(heatmap).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" weekday and hour "),
+    );
+    f.render_widget(heatmap, times[1]);
+
+    // Per day, and what is known about durations and exit codes
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3); 3])
+        .split(rows[2]);
+    let days: Vec<u64> = stats.days.iter().map(|day| day.count as u64).collect();
+    let sparkline = Sparkline::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" runs per day, last 30 days "),
+        )
+        .data(&days)
+        .style(Style::default().fg(Color::Cyan));
+    f.render_widget(sparkline, bottom[0]);
+
+    let longest: Vec<Spans> = stats
+        .longest
+        .iter()
+        .map(|timed| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{:>10} ", stats::duration(timed.duration)),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(stats::one_line(&timed.command)),
+            ])
+        })
+        .collect();
+    let longest = Paragraph::new(longest).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" longest running "),
+    );
+    f.render_widget(longest, bottom[1]);
+
+    let failures: Vec<Spans> = stats
+        .failure_rates
+        .iter()
+        .map(|failures| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{:>5.1}% ", failures.rate() * 100.0),
+                    Style::default().fg(Color::Red),
+                ),
+                Span::raw(format!(
+                    "{} ({} of {})",
+                    failures.binary, failures.failed, failures.runs
+                )),
+            ])
+        })
+        .collect();
+    let failures = Paragraph::new(failures).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" failure rates "),
+    );
+    f.render_widget(failures, bottom[2]);
+}
+
+/// Draws the popup for choosing which words of a command become placeholders.
+fn render_marking<B: Backend>(f: &mut Frame<B>, marking: &Marking) {
+    let area = centered_rect(80, 30, f.size());
+
+    // Style each word by whether it is marked and whether the cursor is on it
+    let mut segments = Vec::new();
+    let mut end = 0;
+    for (i, token) in marking.tokens.iter().enumerate() {
+        segments.push((&marking.command[end..token.range.start], Style::default()));
+        let mut style = Style::default();
+        if marking.marked[i] {
+            style = style.fg(Color::Red).add_modifier(Modifier::BOLD);
         }
+        if i == marking.cursor {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        segments.push((&marking.command[token.range.clone()], style));
+        end = token.range.end;
     }
+    segments.push((&marking.command[end..], Style::default()));
 
-    pub fn fetch(history_file: String) -> History {
-        // @TODO/improvement should definitely figure out
-        // how to use the Result to return a better error
-        // instead of killing the process. This will make
-        // it hard to test.
-        let home_dir = match env::home_dir() {
-            Some(path) => path.display().to_string(),
-            None => "".to_owned(),
-        };
-        if home_dir == "" {
-            println!("Couldn't get home_dir");
-            process::exit(0x0100);
+    let text = Paragraph::new(styled_lines(segments)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" new template: mark the placeholders "),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(text, area);
+}
+
+/// Draws the confirmation of a dangerous command, with what makes it dangerous highlighted.
+fn render_confirm<B: Backend>(f: &mut Frame<B>, confirm: &Confirm) {
+    let risky = Style::default()
+        .fg(Color::Red)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let command = &confirm.command;
+    let mut lines = vec![
+        Spans::from("This command is on the danger list:"),
+        Spans::default(),
+    ];
+    lines.extend(styled_lines(vec![
+        (&command[..confirm.risky.start], Style::default()),
+        (&command[confirm.risky.clone()], risky),
+        (&command[confirm.risky.end..], Style::default()),
+    ]));
+    lines.push(Spans::default());
+    lines.push(Spans::from("Run it anyway? (y/n)"));
+
+    // As tall as the lines are once wrapped, so the question stays in view
+    let mut area = centered_rect(80, 100, f.size());
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    let rows: usize = lines
+        .iter()
+        .map(|line| line.width().max(1).div_ceil(width))
+        .sum();
+    let height = (rows as u16).saturating_add(2).min(area.height);
+    area.y += (area.height - height) / 2;
+    area.height = height;
+
+    let text = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red))
+            .title(" dangerous command "),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(text, area);
+}
+
+/// Turns styled pieces of text into lines, splitting them where they span several.
+fn styled_lines<'a>(segments: Vec<(&'a str, Style)>) -> Vec<Spans<'a>> {
+    let mut lines = vec![Spans::default()];
+    for (text, style) in segments {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Spans::default());
+            }
+            if let Some(line) = lines.last_mut() {
+                line.0.push(Span::styled(part, style));
+            }
         }
+    }
+    lines
+}
 
-        let path = Path::new(home_dir.as_str());
-        let full_path = path.join(history_file.as_str());
-        let contents = fs::read(full_path).expect("Should have been able to read the file");
+/// Draws the form for filling in the placeholders of a template, with the
+/// previously used values of the current one as suggestions.
+fn render_form<B: Backend>(f: &mut Frame<B>, form: &Form, template: &Template) {
+    let area = centered_rect(80, 50, f.size());
+    let name_width = template
+        .placeholders
+        .iter()
+        .map(|p| p.name.width())
+        .max()
+        .unwrap_or(0);
 
-        let history_type: String;
-        if history_file.contains(".zsh_history") {
-            history_type = "zsh".to_string()
-        } else if history_file.contains(".bash_history") {
-            history_type = "bash".to_string()
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            template.display(),
+            Style::default().fg(Color::DarkGray),
+        )),
+        Spans::default(),
+    ];
+    let mut cursor = (area.x, area.y);
+    for (i, (placeholder, value)) in template.placeholders.iter().zip(&form.values).enumerate() {
+        let label = format!("{:>width$}: ", placeholder.name, width = name_width);
+        if i == form.field {
+            cursor = (
+                area.x + 1 + (label.width() + value.width()) as u16,
+                area.y + 1 + lines.len() as u16,
+            );
+            lines.push(Spans::from(vec![
+                Span::styled(label, Style::default().fg(Color::Red)),
+                Span::raw(value.clone()),
+            ]));
+            if !placeholder.values.is_empty() {
+                lines.push(Spans::from(Span::styled(
+                    format!(
+                        "{:width$}  used before: {}",
+                        "",
+                        placeholder.values.join(", "),
+                        width = name_width
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
         } else {
-            println!("Unsupported history type");
-            process::exit(0x0100);
+            lines.push(Spans::from(vec![
+                Span::raw(label),
+                Span::raw(value.clone()),
+            ]));
         }
-        // println!("{:?}", History::from_bytes(contents.clone()).len());
-        // println!("{:?}", path.as_os_str());
-        // println!("{}", history_file);
-        // println!("{}", history_type);
-        process_history(contents, history_type)
     }
 
-    pub fn process_history(history: Vec<u8>, history_type: String) -> History {
-        // @TODO/improvement I don't like how much I'm passing around zsh/bash, this should become
-        // its zsh/bash interfaces built on top of history as a base.
-        if history_type == "zsh" {
-            return reverse(remove_duplicates(remove_empty(remove_timestamps(
-                History::from_bytes(unmetafy(history), history_type),
-            ))));
+    let text = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" fill in template "),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(text, area);
+    f.set_cursor(cursor.0, cursor.1);
+}
+
+/// Draws the popup for editing the note and tags of a favorite.
+fn render_note_form<B: Backend>(f: &mut Frame<B>, form: &NoteForm) {
+    let area = centered_rect(80, 30, f.size());
+    let fields = [("note", &form.note), ("tags", &form.tags)];
+
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            form.command.clone(),
+            Style::default().fg(Color::DarkGray),
+        )),
+        Spans::default(),
+    ];
+    let mut cursor = (area.x, area.y);
+    for (i, (name, value)) in fields.iter().enumerate() {
+        let label = format!("{}: ", name);
+        let mut label_style = Style::default();
+        if i == form.field {
+            label_style = label_style.fg(Color::Red);
+            cursor = (
+                area.x + 1 + (label.width() + value.width()) as u16,
+                area.y + 1 + lines.len() as u16,
+            );
         }
-        reverse(remove_duplicates(remove_empty(History::from_bytes(
-            history,
-            history_type,
-        ))))
-    }
-
-    fn unmetafy(mut bytestring: Vec<u8>) -> Vec<u8> {
-        /* Unmetafying zsh history requires looping over the bytestring, removing
-         * each encountered Meta character, and XOR-ing the following byte with 32.
-         *
-         * For instance:
-         *
-         * Input: ('a', 'b', 'c', Meta, 'd', 'e', 'f')
-         * Wanted: ('a', 'b', 'c', 'd' ^ 32, 'e', 'f')
-         */
-        const ZSH_META: u8 = 0x83;
-
-        for index in (0..bytestring.len()).rev() {
-            if bytestring[index] == ZSH_META {
-                bytestring.remove(index);
-                bytestring[index] ^= 32;
-            }
-        }
-        bytestring
-    }
-
-    fn remove_timestamps(mut history: History) -> History {
-        /* The metadata in the .zsh_history file looks like:
-         *
-         * : 1330648651:0;sudo reboot
-         *
-         * I strip it in from_bytes() by "\n: " so it better
-         * handles multiline commands. So this will only
-         * strip by what is left after that parsing:
-         *
-         * 1330648651:0;sudo reboot
-         *
-         * So the command it get after parsing is:
-         *
-         * sudo reboot
-         */
-        //   : 1330648651:0;sudo reboot
-
-        // Special case: need to handle the first element in the history
-        // since it doesn't have a new line, so it wasn't parsed at all
-        // in from_bytes().
-        // @TODO/improvement I don't like having to do this, come up with
-        // a better way.
-        let regex_first = Regex::new(r"^: \d{10}:\d;").unwrap();
-        let first = history.get(0);
-        let val = regex_first.replace(first.unwrap(), "").to_owned();
-        history[0] = val.to_string();
-
-        let regex_rest = Regex::new(r"^\d{10}:\d;").unwrap();
-        history
-            .iter()
-            .map(|line| regex_rest.replace(line, "").into_owned())
-            .collect()
+        lines.push(Spans::from(vec![
+            Span::styled(label, label_style),
+            Span::raw(value.to_string()),
+        ]));
     }
 
-    fn remove_empty(mut history: History) -> History {
-        history.retain(|line| line != "");
-        history
-    }
+    let text =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" favorite "));
+    f.render_widget(Clear, area);
+    f.render_widget(text, area);
+    f.set_cursor(cursor.0, cursor.1);
+}
+
+/// All the keys of a binding, e.g. `Home, g`.
+fn keys_label(binding: &keymap::Binding) -> String {
+    binding
+        .keys
+        .iter()
+        .map(|key| key.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns a rectangle taking up the given percentages of `area`, centered in it.
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vertical[1])[1]
+}
 
-    fn reverse(mut history: History) -> History {
-        history.reverse();
-        history
+/// Builds the status bar: matched and total counts, search mode, sort order,
+/// scope, source filter, time period and ignored entries, how long the last filter took and which history sources are loaded.
+fn status_bar(app: &App) -> Paragraph<'static> {
+    let separator = || Span::styled(" │ ", Style::default().fg(Color::DarkGray));
+    let error_style = Style::default().fg(Color::Red);
+
+    let mut spans = vec![
+        Span::styled(
+            format!(
+                "{}/{}",
+                format_count(app.match_count()),
+                format_count(app.full_history.len())
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        separator(),
+        Span::raw(app.search_mode.name()),
+        separator(),
+        Span::raw(format!("sort: {}", app.sort_order.name())),
+        separator(),
+        Span::raw(format!("scope: {}", app.scope.name())),
+    ];
+    if app.grouped {
+        let groups = app
+            .items
+            .items()
+            .iter()
+            .filter(|item| item.level != Level::Member);
+        spans.push(separator());
+        spans.push(Span::raw(format!(
+            "{} groups",
+            format_count(groups.count())
+        )));
+    }
+    if let Some(source) = app.source_filter {
+        spans.push(separator());
+        spans.push(Span::raw(format!("source: {}", app.source_labels[source])));
+    }
+    if !app.narrowed.is_all() {
+        spans.push(separator());
+        spans.push(Span::raw(format!("time: {}", app.narrowed.describe())));
+    }
+    if app.is_loading() {
+        spans.push(separator());
+        spans.push(Span::styled(
+            "loading…",
+            Style::default().add_modifier(Modifier::ITALIC),
+        ));
+    }
+    if app.ignored > 0 {
+        spans.push(separator());
+        spans.push(Span::raw(if app.show_ignored {
+            format!("showing {} ignored", format_count(app.ignored))
+        } else {
+            format!("{} ignored", format_count(app.ignored))
+        }));
+    }
+    if let Some(took) = app.filter_time {
+        spans.push(separator());
+        spans.push(Span::raw(format!("{:.1} ms", took.as_secs_f64() * 1000.0)));
+    }
+    if let Some(err) = &app.filter_error {
+        spans.push(separator());
+        spans.push(Span::styled(err.clone(), error_style));
     }
+    if let Some(notice) = &app.notice {
+        spans.push(separator());
+        spans.push(Span::styled(
+            notice.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+    }
+    for source in &app.sources {
+        spans.push(separator());
+        spans.push(match &source.loaded {
+            Ok(count) => Span::raw(format!("{} ({})", source.name, format_count(*count))),
+            Err(err) => Span::styled(format!("{}: {}", source.name, err), error_style),
+        });
+    }
+    Paragraph::new(Spans::from(spans))
+}
 
-    fn remove_duplicates(mut history: History) -> History {
-        history = history.into_iter().unique().collect();
-        history
+/// Formats a count with spaces between groups of thousands, e.g. `58 003`.
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(' ');
+        }
+        formatted.push(digit);
     }
+    formatted
+}
+
+/// Number of terminal rows an entry takes up in the list.
+fn item_height(item: &str) -> usize {
+    item.lines().count()
 }
+
+#[cfg(test)]
+mod tests;
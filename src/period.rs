@@ -0,0 +1,312 @@
+//! Narrowing the history to when commands were run, with `--since` and
+//! `--until`, or with `@` words in the query like `@yesterday`.
+//!
+//! Both take the same expressions: `today`, `yesterday`, a weekday for the
+//! last one before today, a year, month or day like `2024`, `2024-03` or
+//! `2024-03-05`, or a time ago like `30m`, `12h`, `3d`, `2w`, `6mo` or `1y`.
+//! In a query, a time ago is the time since then, `@<2w` too, and `@>2w` is
+//! everything before it.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Weekday};
+
+/// A span of time, from `since` up to but not including `until`, in seconds
+/// since the epoch. Either end may be open.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Period {
+    /// The start of the period, if it has one.
+    pub since: Option<u64>,
+    /// The end of the period, if it has one.
+    pub until: Option<u64>,
+}
+
+impl Period {
+    /// Whether the period is all of time, which doesn't narrow anything.
+    pub fn is_all(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// Whether a command run at `timestamp` was run in the period. When and
+    /// whether commands without a timestamp were is unknown, so they are
+    /// only in all of time.
+    pub fn contains(&self, timestamp: Option<u64>) -> bool {
+        if self.is_all() {
+            return true;
+        }
+        timestamp.is_some_and(|time| {
+            self.since.is_none_or(|since| time >= since)
+                && self.until.is_none_or(|until| time < until)
+        })
+    }
+
+    /// The part of the period that is also in `other`.
+    pub fn and(self, other: Period) -> Period {
+        let latest = |a: Option<u64>, b: Option<u64>| a.max(b);
+        let earliest = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Period {
+            since: latest(self.since, other.since),
+            until: earliest(self.until, other.until),
+        }
+    }
+
+    /// Describes the period for the status bar, e.g. `2024-03-01 – 2024-03-31`
+    /// or `since 2024-03-05 14:30`.
+    pub fn describe(&self) -> String {
+        let since = self.since.map(|since| format_time(since, false));
+        let until = self.until.map(|until| format_time(until, true));
+        match (since, until) {
+            (Some(since), Some(until)) if since == until => since,
+            (Some(since), Some(until)) => format!("{} – {}", since, until),
+            (Some(since), None) => format!("since {}", since),
+            (None, Some(until)) => format!("until {}", until),
+            (None, None) => "all time".to_string(),
+        }
+    }
+}
+
+/// Formats `time` as a day when it is midnight and as a minute otherwise.
+/// The end of a period isn't in it, so when it is midnight, it is shown as
+/// the day before, the last one in the period.
+fn format_time(time: u64, end: bool) -> String {
+    let Some(local) = Local.timestamp_opt(time as i64, 0).single() else {
+        return time.to_string();
+    };
+    if local.time() != chrono::NaiveTime::MIN {
+        return local.format("%Y-%m-%d %H:%M").to_string();
+    }
+    let day = if end {
+        local.date_naive().pred_opt().unwrap_or(local.date_naive())
+    } else {
+        local.date_naive()
+    };
+    day.format("%Y-%m-%d").to_string()
+}
+
+/// What an expression names: a span of time like a day or a month, or a
+/// point in time some time ago.
+enum Expr {
+    Span(Period),
+    Ago(u64),
+}
+
+fn parse_expr(expr: &str, now: DateTime<Local>) -> Option<Expr> {
+    let expr = expr.to_ascii_lowercase();
+    let today = now.date_naive();
+    let day = |day: NaiveDate| span(day, day.succ_opt()?);
+    match expr.as_str() {
+        "today" => return day(today).map(Expr::Span),
+        "yesterday" => return day(today.pred_opt()?).map(Expr::Span),
+        _ => {}
+    }
+    if let Ok(weekday) = expr.parse::<Weekday>() {
+        let back =
+            (today.weekday().num_days_from_monday() + 6 - weekday.num_days_from_monday()) % 7 + 1;
+        return day(today - Duration::days(back.into())).map(Expr::Span);
+    }
+    if let Some(age) = parse_age(&expr) {
+        let then = now.timestamp().checked_sub(age)?;
+        return Some(Expr::Ago(then.try_into().ok()?));
+    }
+
+    let digits =
+        |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+    let parts: Vec<&str> = expr.split('-').collect();
+    let number = |i: usize| parts[i].parse::<u32>().ok();
+    match parts[..] {
+        [year] if digits(year, 4) => {
+            let year = year.parse().ok()?;
+            span(
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+            )
+        }
+        [year, month] if digits(year, 4) && digits(month, 2) => {
+            let first = NaiveDate::from_ymd_opt(year.parse().ok()?, number(1)?, 1)?;
+            span(first, first.checked_add_months(chrono::Months::new(1))?)
+        }
+        [year, _, _] if digits(year, 4) && digits(parts[1], 2) && digits(parts[2], 2) => day(
+            NaiveDate::from_ymd_opt(year.parse().ok()?, number(1)?, number(2)?)?,
+        ),
+        _ => None,
+    }
+    .map(Expr::Span)
+}
+
+/// Parses a time ago like `2w` into seconds.
+fn parse_age(expr: &str) -> Option<i64> {
+    let split = expr.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = expr.split_at(split);
+    let count: i64 = count.parse().ok()?;
+    let unit = match unit {
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "mo" => 30 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    count.checked_mul(unit)
+}
+
+/// The days from `first` up to `after`, from midnight to midnight.
+fn span(first: NaiveDate, after: NaiveDate) -> Option<Period> {
+    Some(Period {
+        since: Some(midnight(first)?),
+        until: Some(midnight(after)?),
+    })
+}
+
+fn midnight(day: NaiveDate) -> Option<u64> {
+    let time = Local.from_local_datetime(&day.and_time(chrono::NaiveTime::MIN));
+    time.earliest()?.timestamp().try_into().ok()
+}
+
+const EXPECTED: &str =
+    "expected today, yesterday, a weekday, a date like 2024-03-05 or 2024-03, or a time ago like 2w";
+
+/// Parses the argument of `--since` into when it starts.
+pub fn since(expr: &str) -> Result<u64, String> {
+    match parse_expr(expr, Local::now()) {
+        Some(Expr::Span(period)) => Ok(period.since.unwrap_or_default()),
+        Some(Expr::Ago(time)) => Ok(time),
+        None => Err(EXPECTED.to_string()),
+    }
+}
+
+/// Parses the argument of `--until` into when it ends, so that
+/// `--until 2024-03` includes all of March.
+pub fn until(expr: &str) -> Result<u64, String> {
+    match parse_expr(expr, Local::now()) {
+        Some(Expr::Span(period)) => Ok(period.until.unwrap_or(u64::MAX)),
+        Some(Expr::Ago(time)) => Ok(time),
+        None => Err(EXPECTED.to_string()),
+    }
+}
+
+/// Parses what follows the `@` of a word in a query.
+pub fn parse(expr: &str, now: DateTime<Local>) -> Option<Period> {
+    let (comparison, age) = match expr.strip_prefix(['<', '>']) {
+        Some(age) => (expr.chars().next(), age),
+        None => (None, expr),
+    };
+    match (comparison, parse_expr(age, now)?) {
+        (None, Expr::Span(period)) => Some(period),
+        // Before or after a day could be either end of it
+        (Some(_), Expr::Span(_)) => None,
+        (Some('>'), Expr::Ago(time)) => Some(Period {
+            since: None,
+            until: Some(time),
+        }),
+        (_, Expr::Ago(time)) => Some(Period {
+            since: Some(time),
+            until: None,
+        }),
+    }
+}
+
+/// Takes the `@` words out of `query`, returning the rest of it and the
+/// period they narrow to together. Words starting with `@` that aren't one
+/// of the expressions, like `@types/node`, are left in the query.
+pub fn split_query(query: &str, now: DateTime<Local>) -> (String, Period) {
+    let mut period = Period::default();
+    let mut taken = false;
+    let rest: Vec<&str> = query
+        .split(' ')
+        .filter(
+            |word| match word.strip_prefix('@').and_then(|expr| parse(expr, now)) {
+                Some(narrowed) => {
+                    period = period.and(narrowed);
+                    taken = true;
+                    false
+                }
+                None => true,
+            },
+        )
+        .collect();
+    if !taken {
+        return (query.to_string(), period);
+    }
+    (rest.join(" ").trim().to_string(), period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, 0, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    fn at(year: i32, month: u32, day: u32) -> Option<u64> {
+        Some(local(year, month, day, 0).timestamp() as u64)
+    }
+
+    #[test]
+    fn parses_days_months_and_ages() {
+        // A Thursday afternoon
+        let now = local(2024, 3, 14, 15);
+        let day = |y, m, d, y2, m2, d2| Period {
+            since: at(y, m, d),
+            until: at(y2, m2, d2),
+        };
+        assert_eq!(parse("today", now), Some(day(2024, 3, 14, 2024, 3, 15)));
+        assert_eq!(parse("Yesterday", now), Some(day(2024, 3, 13, 2024, 3, 14)));
+        assert_eq!(parse("tuesday", now), Some(day(2024, 3, 12, 2024, 3, 13)));
+        assert_eq!(parse("thu", now), Some(day(2024, 3, 7, 2024, 3, 8)));
+        assert_eq!(parse("2024-02", now), Some(day(2024, 2, 1, 2024, 3, 1)));
+        assert_eq!(
+            parse("2023-12-31", now),
+            Some(day(2023, 12, 31, 2024, 1, 1))
+        );
+        assert_eq!(parse("2023", now), Some(day(2023, 1, 1, 2024, 1, 1)));
+
+        let two_weeks_ago = Some((now - Duration::weeks(2)).timestamp() as u64);
+        let within = Period {
+            since: two_weeks_ago,
+            until: None,
+        };
+        assert_eq!(parse("2w", now), Some(within));
+        assert_eq!(parse("<2w", now), Some(within));
+        assert_eq!(
+            parse(">2w", now),
+            Some(Period {
+                since: None,
+                until: two_weeks_ago
+            })
+        );
+
+        for invalid in ["", "2", "2q", ">today", "2024-13", "2024-3", "types/node"] {
+            assert_eq!(parse(invalid, now), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn takes_time_out_of_queries() {
+        let now = local(2024, 3, 14, 15);
+        let (query, period) = split_query("migrate @2024-03 @>1d", now);
+        assert_eq!(query, "migrate");
+        assert_eq!(period.since, at(2024, 3, 1));
+        assert_eq!(
+            period.until,
+            Some((now - Duration::days(1)).timestamp() as u64)
+        );
+        assert_eq!(period.describe(), "2024-03-01 – 2024-03-13 15:00");
+
+        let (query, period) = split_query("npm i @types/node ", now);
+        assert_eq!(query, "npm i @types/node ");
+        assert!(period.is_all());
+
+        let (_, yesterday) = split_query("@yesterday", now);
+        assert_eq!(yesterday.describe(), "2024-03-13");
+        assert!(yesterday.contains(Some(local(2024, 3, 13, 23).timestamp() as u64)));
+        assert!(!yesterday.contains(at(2024, 3, 14)));
+        assert!(!yesterday.contains(None));
+        assert!(Period::default().contains(None));
+    }
+}
@@ -0,0 +1,301 @@
+//! The list the interface picks a command from: what it lists, which row
+//! is selected and how far it is scrolled.
+
+use crate::favorites::Favorite;
+use crate::history::Entry;
+use crate::shell;
+use crate::template::Template;
+use std::collections::{HashMap, HashSet};
+
+/// The state of a list to pick an item from: the items, which one is
+/// selected and how far the list is scrolled. Drawing is left to the
+/// interface, which tells the picker how tall it is with `update_offset`.
+pub struct Picker<T> {
+    items: Vec<T>,
+    selected: usize,
+    // Index of the first item drawn, so that a click on a row can be mapped
+    // back to the item drawn there.
+    offset: usize,
+}
+
+impl<T> Picker<T> {
+    /// A picker of `items` with the first one selected.
+    pub fn new(items: Vec<T>) -> Picker<T> {
+        Picker {
+            items,
+            selected: 0,
+            offset: 0,
+        }
+    }
+
+    /// All the items, in the order they are listed.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// The selected item, or `None` when there are no items.
+    pub fn selected(&self) -> Option<&T> {
+        self.items.get(self.selected)
+    }
+
+    /// The index of the selected item.
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    /// Index of the first item drawn, as of the last `update_offset`.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Selects the next item, wrapping around to the first one.
+    pub fn next(&mut self) {
+        self.selected = if self.items.is_empty() || self.selected >= self.items.len() - 1 {
+            0
+        } else {
+            self.selected + 1
+        };
+    }
+
+    /// Selects the previous item, wrapping around to the last one.
+    pub fn previous(&mut self) {
+        self.selected = if self.items.is_empty() {
+            0
+        } else if self.selected == 0 {
+            self.items.len() - 1
+        } else {
+            self.selected - 1
+        };
+    }
+
+    /// Moves the selection `n` items down, stopping at the last item instead of wrapping.
+    pub fn forward(&mut self, n: usize) {
+        self.select(self.selected.saturating_add(n));
+    }
+
+    /// Moves the selection `n` items up, stopping at the first item instead of wrapping.
+    pub fn backward(&mut self, n: usize) {
+        self.select(self.selected.saturating_sub(n));
+    }
+
+    /// Selects the first item.
+    pub fn first(&mut self) {
+        self.select(0);
+    }
+
+    /// Selects the last item.
+    pub fn last(&mut self) {
+        self.select(usize::MAX);
+    }
+
+    /// Replaces the items, keeping the list scrolled where it was and the
+    /// same row selected, as far as there are still enough items for that.
+    pub fn replace(&mut self, items: Vec<T>) {
+        self.items = items;
+        self.offset = self.offset.min(self.items.len().saturating_sub(1));
+        self.select(self.selected);
+    }
+
+    /// Selects the item at `index`, clamped to the bounds of the list.
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+    }
+
+    /// Scrolls the list just enough for the selected item to be drawn, the
+    /// way ratatui's `List` does, given the height of the list area and of
+    /// every item.
+    pub fn update_offset(&mut self, max_height: usize, height_of: impl Fn(&T) -> usize) {
+        if self.items.is_empty() || max_height == 0 {
+            return;
+        }
+
+        let mut start = self.offset.min(self.items.len() - 1);
+        let mut end = start;
+        let mut height = 0;
+        for item in self.items.iter().skip(start) {
+            if height + height_of(item) > max_height {
+                break;
+            }
+            height += height_of(item);
+            end += 1;
+        }
+
+        let selected = self.selected.min(self.items.len() - 1);
+        while selected >= end {
+            height = height.saturating_add(height_of(&self.items[end]));
+            end += 1;
+            while height > max_height {
+                height = height.saturating_sub(height_of(&self.items[start]));
+                start += 1;
+            }
+        }
+        while selected < start {
+            start -= 1;
+            height = height.saturating_add(height_of(&self.items[start]));
+            while height > max_height {
+                end -= 1;
+                height = height.saturating_sub(height_of(&self.items[end]));
+            }
+        }
+        self.offset = start;
+    }
+
+    /// Returns the index of the item drawn on `row`, counted from the top of the list area.
+    pub fn index_at(&self, row: usize, height_of: impl Fn(&T) -> usize) -> Option<usize> {
+        let mut top = 0;
+        for (i, item) in self.items.iter().enumerate().skip(self.offset) {
+            top += height_of(item);
+            if row < top {
+                return Some(i);
+            }
+        }
+        None
+    }
+}
+
+/// A row of the list: a command, and what it is.
+#[derive(Clone)]
+pub struct Item {
+    /// The command shown, or the name of a group.
+    pub command: String,
+    /// What the row is.
+    pub kind: Kind,
+    /// Where the row is in the grouped view.
+    pub level: Level,
+}
+
+/// What a row of the list is, and where it comes from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    /// An entry of the merged history, by its index.
+    Entry(usize),
+    /// A saved template, by its index.
+    Template(usize),
+    /// A favorite that is no longer in the history.
+    Favorite,
+    /// The header of a group in the grouped view.
+    Group,
+}
+
+/// Where a row of the list is in the grouped view.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Level {
+    /// A row of a list that isn't grouped, or an entry alone in its group.
+    Top,
+    /// The header of a group, whose command is the name of the group.
+    Group {
+        /// How many entries the group has.
+        size: usize,
+        /// Whether they are listed under the header.
+        expanded: bool,
+    },
+    /// An entry listed under the header of its group.
+    Member,
+}
+
+impl Item {
+    /// Whether the row is the header of a group.
+    pub fn is_group(&self) -> bool {
+        matches!(self.level, Level::Group { .. })
+    }
+
+    /// The index of the history entry this row is, if it is one.
+    pub fn entry(&self) -> Option<usize> {
+        match self.kind {
+            Kind::Entry(entry) => Some(entry),
+            _ => None,
+        }
+    }
+
+    /// The index of the template this row is, if it is one.
+    pub fn template(&self) -> Option<usize> {
+        match self.kind {
+            Kind::Template(template) => Some(template),
+            _ => None,
+        }
+    }
+}
+
+/// The group of the grouped view `command` belongs to: the program it runs
+/// with its subcommand, like `docker run`, or the whole command when it runs
+/// none.
+pub fn group_name(command: &str) -> String {
+    match shell::program(command) {
+        Some((program, Some(subcommand))) => format!("{} {}", program, subcommand),
+        Some((program, None)) => program.to_string(),
+        None => command.trim().to_string(),
+    }
+}
+
+/// Groups `items` by their `group_name`, in the order the groups first
+/// appear in, so that the best match comes first. Each group is listed as a
+/// header row followed by its items if the group is in `expanded`, unless
+/// it has a single item, which is listed on its own.
+pub fn group(items: &[Item], expanded: &HashSet<String>) -> Vec<Item> {
+    let mut names: Vec<String> = Vec::new();
+    let mut groups: HashMap<String, Vec<&Item>> = HashMap::new();
+    for item in items {
+        let name = group_name(&item.command);
+        if !groups.contains_key(&name) {
+            names.push(name.clone());
+        }
+        groups.entry(name).or_default().push(item);
+    }
+
+    let mut rows = Vec::with_capacity(names.len());
+    for name in names {
+        let members = &groups[&name];
+        if let [item] = members[..] {
+            rows.push(Item {
+                level: Level::Top,
+                ..item.clone()
+            });
+            continue;
+        }
+        let open = expanded.contains(&name);
+        rows.push(Item {
+            command: name,
+            kind: Kind::Group,
+            level: Level::Group {
+                size: members.len(),
+                expanded: open,
+            },
+        });
+        if open {
+            rows.extend(members.iter().map(|item| Item {
+                level: Level::Member,
+                ..(*item).clone()
+            }));
+        }
+    }
+    rows
+}
+
+/// Everything there is to pick from: the templates, then the history, then
+/// the favorites that are no longer in it.
+pub fn list_items(templates: &[Template], history: &[Entry], favorites: &[Favorite]) -> Vec<Item> {
+    let in_history: HashSet<&str> = history.iter().map(|entry| entry.command.as_str()).collect();
+    let lost_favorites = favorites
+        .iter()
+        .filter(|favorite| !in_history.contains(favorite.command.as_str()))
+        .map(|favorite| Item {
+            command: favorite.command.clone(),
+            kind: Kind::Favorite,
+            level: Level::Top,
+        });
+    templates
+        .iter()
+        .enumerate()
+        .map(|(i, template)| Item {
+            command: template.display(),
+            kind: Kind::Template(i),
+            level: Level::Top,
+        })
+        .chain(history.iter().enumerate().map(|(i, entry)| Item {
+            command: entry.command.clone(),
+            kind: Kind::Entry(i),
+            level: Level::Top,
+        }))
+        .chain(lost_favorites)
+        .collect()
+}
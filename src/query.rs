@@ -0,0 +1,311 @@
+//! `hui search`: the search box's search without the interface.
+
+use crate::export::Record;
+use crate::favorites::Favorite;
+use crate::history::Entry;
+use crate::period::{self, Period};
+use crate::search::{self, SearchMode, SortOrder};
+use chrono::Local;
+use clap::ValueEnum;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+/// How `hui search` prints its matches.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One match per line, after any requested columns
+    Plain,
+    /// A JSON array of matches, with everything known about them
+    Json,
+    /// One JSON match per line
+    Ndjson,
+}
+
+/// Extra information `hui search` can print before each command.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    /// When the command was run, in seconds since the epoch
+    Timestamp,
+    /// The directory it was run in
+    Cwd,
+    /// The exit status it had
+    Exit,
+    /// How long it took, in milliseconds
+    Duration,
+    /// The history sources it was found in
+    Sources,
+    /// How many times it was run
+    Runs,
+}
+
+/// The options of `hui search`: the query and how its matches are printed.
+#[derive(clap::Args)]
+pub struct SearchArgs {
+    /// What to search for; an empty query matches everything
+    #[arg(default_value = "")]
+    pub query: String,
+    #[arg(long, value_enum, default_value = "fuzzy")]
+    /// How the query is matched
+    pub mode: SearchMode,
+    #[arg(long, value_enum, default_value = "relevance")]
+    /// The order matches are printed in
+    pub sort: SortOrder,
+    /// Print at most this many matches
+    #[arg(long)]
+    pub limit: Option<usize>,
+    #[arg(long, value_enum, default_value = "plain")]
+    /// How each match is printed
+    pub format: OutputFormat,
+    /// End each match with a NUL character instead of a newline
+    #[arg(long)]
+    pub print0: bool,
+    /// Information to print before each command, separated by tabs
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub columns: Vec<Column>,
+    /// Only search the history source with this name, e.g. zsh
+    #[arg(long)]
+    pub source: Option<String>,
+    #[arg(skip)]
+    /// Only search favorites, from the global `--favorites`
+    pub favorites: bool,
+    #[arg(skip)]
+    /// Only search commands run in this period, from `--since` and `--until`
+    pub period: Period,
+}
+
+/// Runs `hui search`: filters the history the same way the search box does
+/// and prints the matches, best first. Returns how many were printed.
+pub fn run(
+    args: &SearchArgs,
+    history: &[Entry],
+    labels: &[String],
+    favorites: &[Favorite],
+    out: &mut dyn Write,
+) -> Result<usize, String> {
+    let source = match &args.source {
+        Some(name) => match labels.iter().position(|label| label == name) {
+            Some(source) => Some(source),
+            None => return Err(format!("no history source is called {}", name)),
+        },
+        None => None,
+    };
+    // `@` words narrow the search to when commands were run, within --since and --until
+    let (query, period) = period::split_query(&args.query, Local::now());
+    let period = period.and(args.period);
+
+    // Favorites are also found by their note and tags, and are searched even
+    // when they are no longer in any history
+    let in_history: HashSet<&str> = history.iter().map(|entry| entry.command.as_str()).collect();
+    let lost_favorites = favorites
+        .iter()
+        .map(|favorite| favorite.command.as_str())
+        .filter(|command| !in_history.contains(command))
+        .map(|command| (command, None));
+    let favorites: HashMap<&str, &Favorite> = favorites
+        .iter()
+        .map(|favorite| (favorite.command.as_str(), favorite))
+        .collect();
+    let candidates: Vec<(&str, Option<&Entry>)> = history
+        .iter()
+        .map(|entry| (entry.command.as_str(), Some(entry)))
+        .chain(lost_favorites)
+        .filter(|(command, _)| !args.favorites || favorites.contains_key(command))
+        .filter(|(_, entry)| match source {
+            Some(source) => entry.is_some_and(|entry| entry.sources.contains(&source)),
+            None => true,
+        })
+        .filter(|(_, entry)| period.is_all() || entry.is_some_and(|e| period.contains(e.timestamp)))
+        .collect();
+
+    let matches = search::filter(
+        &SkimMatcherV2::default(),
+        &candidates,
+        |(command, _)| match favorites.get(command) {
+            Some(favorite) => Cow::Owned(favorite.searchable()),
+            None => Cow::Borrowed(command),
+        },
+        &query,
+        args.mode,
+        args.sort,
+    )
+    .map_err(|err| err.to_string())?;
+
+    let records = matches
+        .into_iter()
+        .take(args.limit.unwrap_or(usize::MAX))
+        .map(|(command, entry)| match entry {
+            Some(entry) => Record::new(entry, labels),
+            None => Record {
+                command: command.to_string(),
+                timestamp: None,
+                context: Default::default(),
+                sources: Vec::new(),
+                runs: 0,
+            },
+        });
+    print(args, records, out).map_err(|err| err.to_string())
+}
+
+fn print(
+    args: &SearchArgs,
+    records: impl Iterator<Item = Record>,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let terminator = if args.print0 { "\0" } else { "\n" };
+    let mut count = 0;
+    match args.format {
+        OutputFormat::Plain => {
+            for record in records {
+                for column in &args.columns {
+                    write!(out, "{}\t", cell(&record, *column))?;
+                }
+                write!(out, "{}{}", record.command, terminator)?;
+                count += 1;
+            }
+        }
+        OutputFormat::Json => {
+            let records: Vec<Record> = records.collect();
+            serde_json::to_writer_pretty(&mut *out, &records)?;
+            writeln!(out)?;
+            count = records.len();
+        }
+        OutputFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *out, &record)?;
+                write!(out, "{}", terminator)?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// The value of `column` for `record`, or `-` when it isn't known.
+fn cell(record: &Record, column: Column) -> String {
+    let value = match column {
+        Column::Timestamp => record.timestamp.map(|t| t.to_string()),
+        Column::Cwd => record.context.cwd.clone(),
+        Column::Exit => record.context.exit.map(|e| e.to_string()),
+        Column::Duration => record.context.duration.map(|d| d.to_string()),
+        Column::Sources => Some(record.sources.join(",")).filter(|s| !s.is_empty()),
+        Column::Runs => Some(record.runs.to_string()),
+    };
+    value.unwrap_or_else(|| "-".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Context;
+    use crate::period::Period;
+    use crate::search::{SearchMode, SortOrder};
+
+    fn args(query: &str) -> SearchArgs {
+        SearchArgs {
+            query: query.to_string(),
+            mode: SearchMode::Fuzzy,
+            sort: SortOrder::Relevance,
+            limit: None,
+            format: OutputFormat::Plain,
+            print0: false,
+            columns: Vec::new(),
+            source: None,
+            favorites: false,
+            period: Period::default(),
+        }
+    }
+
+    /// Searches a small history from two sources, most recent first.
+    fn search(args: &SearchArgs) -> Result<(usize, String), String> {
+        let entry = |command: &str, timestamp, sources: Vec<usize>| Entry {
+            command: command.to_string(),
+            timestamp: Some(timestamp),
+            sources,
+            context: Context {
+                exit: Some(0),
+                ..Context::default()
+            },
+            runs: 1,
+            ignored: false,
+        };
+        let history = [
+            entry("git push", 300, vec![0]),
+            entry("cargo test", 200, vec![1]),
+            entry("git status", 100, vec![0, 1]),
+        ];
+        let labels = ["zsh".to_string(), "bash".to_string()];
+        let mut favorite = Favorite::new("kubectl get pods");
+        favorite.tags.push("k8s".to_string());
+        let mut out = Vec::new();
+        let count = run(args, &history, &labels, &[favorite], &mut out)?;
+        Ok((count, String::from_utf8(out).unwrap()))
+    }
+
+    #[test]
+    fn prints_matches_best_first() {
+        assert_eq!(
+            search(&args("stat")).unwrap(),
+            (1, "git status\n".to_string())
+        );
+
+        let mut args = args("git");
+        args.sort = SortOrder::Recency;
+        args.limit = Some(1);
+        assert_eq!(search(&args).unwrap(), (1, "git push\n".to_string()));
+    }
+
+    #[test]
+    fn nothing_matching_prints_nothing() {
+        assert_eq!(search(&args("zzz")).unwrap(), (0, String::new()));
+    }
+
+    #[test]
+    fn prints_columns_and_nul_terminators() {
+        let mut args = args("git");
+        args.sort = SortOrder::Recency;
+        args.columns = vec![Column::Timestamp, Column::Sources, Column::Cwd];
+        args.print0 = true;
+        let (_, out) = search(&args).unwrap();
+        assert_eq!(
+            out,
+            "300\tzsh\t-\tgit push\x00100\tzsh,bash\t-\tgit status\0"
+        );
+    }
+
+    #[test]
+    fn filters_by_source_and_favorites() {
+        let mut by_source = args("");
+        by_source.source = Some("bash".to_string());
+        by_source.sort = SortOrder::Recency;
+        let (_, out) = search(&by_source).unwrap();
+        assert_eq!(out, "cargo test\ngit status\n");
+
+        by_source.source = Some("fish".to_string());
+        assert!(search(&by_source).is_err());
+
+        // Favorites are found by their tags even when they aren't in the history
+        let mut favorites = args("#k8s");
+        favorites.favorites = true;
+        assert_eq!(search(&favorites).unwrap().1, "kubectl get pods\n");
+    }
+
+    #[test]
+    fn filters_by_time() {
+        let mut since = args("git");
+        since.period.since = Some(200);
+        assert_eq!(search(&since).unwrap().1, "git push\n");
+
+        // Favorites that aren't in the history were never run in any period
+        let mut until = args("");
+        until.sort = SortOrder::Recency;
+        until.period.until = Some(300);
+        assert_eq!(search(&until).unwrap().1, "cargo test\ngit status\n");
+
+        // Run well over a day ago, whenever the test runs
+        let (_, out) = search(&args("git @>1d")).unwrap();
+        assert_eq!(out, "git push\ngit status\n");
+        assert_eq!(search(&args("git @today")).unwrap().0, 0);
+    }
+}
@@ -0,0 +1,200 @@
+//! Filtering the history with a query and ranking what matched.
+
+use clap::ValueEnum;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// How the search input is matched against the history.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SearchMode {
+    /// Every character of the query, in order, scored by how well they match.
+    Fuzzy,
+    /// The query as it is.
+    Substring,
+    /// The query as a regular expression.
+    Regex,
+}
+
+impl SearchMode {
+    /// The mode `Ctrl-t` switches to.
+    pub fn next(self) -> SearchMode {
+        match self {
+            SearchMode::Fuzzy => SearchMode::Substring,
+            SearchMode::Substring => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Fuzzy,
+        }
+    }
+
+    /// The name of the mode, as shown in the status bar.
+    pub fn name(self) -> &'static str {
+        match self {
+            SearchMode::Fuzzy => "fuzzy",
+            SearchMode::Substring => "substring",
+            SearchMode::Regex => "regex",
+        }
+    }
+}
+
+/// The order matches are listed in.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// Best match first.
+    Relevance,
+    /// Most recently run first, i.e. history order.
+    Recency,
+}
+
+impl SortOrder {
+    /// The order `Ctrl-o` switches to.
+    pub fn toggle(self) -> SortOrder {
+        match self {
+            SortOrder::Relevance => SortOrder::Recency,
+            SortOrder::Recency => SortOrder::Relevance,
+        }
+    }
+
+    /// The name of the order, as shown in the status bar.
+    pub fn name(self) -> &'static str {
+        match self {
+            SortOrder::Relevance => "relevance",
+            SortOrder::Recency => "recency",
+        }
+    }
+}
+
+/// Which entries are searched.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Every entry.
+    All,
+    /// Only starred entries.
+    Favorites,
+}
+
+impl Scope {
+    /// The name of the scope, as shown in the status bar.
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::All => "all",
+            Scope::Favorites => "favorites",
+        }
+    }
+}
+
+/// Returns the entries matching `query`, ordered by `sort`. Each entry is
+/// matched on the text `searchable` returns for it.
+///
+/// An empty query matches everything. Substring and regex matching are
+/// case-insensitive unless the query contains an uppercase letter.
+pub fn filter<'a, T: ?Sized>(
+    matcher: &SkimMatcherV2,
+    entries: impl IntoIterator<Item = &'a T>,
+    searchable: impl Fn(&'a T) -> Cow<'a, str>,
+    query: &str,
+    mode: SearchMode,
+    sort: SortOrder,
+) -> Result<Vec<&'a T>, regex::Error> {
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let entries = entries.into_iter().map(|entry| (entry, searchable(entry)));
+    let mut matches: Vec<(i64, &T)> = match mode {
+        SearchMode::Fuzzy => entries
+            .filter_map(|(entry, s)| matcher.fuzzy_match(&s, query).map(|score| (score, entry)))
+            .collect(),
+        SearchMode::Substring => {
+            let query = if ignore_case {
+                query.to_lowercase()
+            } else {
+                query.to_string()
+            };
+            entries
+                .filter_map(|(entry, s)| {
+                    let position = if ignore_case {
+                        s.to_lowercase().find(&query)
+                    } else {
+                        s.find(&query)
+                    };
+                    // Matches closer to the start of the command rank higher
+                    position.map(|position| (-(position as i64), entry))
+                })
+                .collect()
+        }
+        SearchMode::Regex => {
+            let regex = RegexBuilder::new(query)
+                .case_insensitive(ignore_case)
+                .build()?;
+            entries
+                .filter_map(|(entry, s)| regex.find(&s).map(|m| (-(m.start() as i64), entry)))
+                .collect()
+        }
+    };
+
+    if sort == SortOrder::Relevance {
+        // Sort by match score in descending order. The sort is stable, so
+        // equally good matches stay in history order.
+        matches.sort_by(|(score_a, _), (score_b, _)| score_b.cmp(score_a));
+    }
+    Ok(matches.into_iter().map(|(_, entry)| entry).collect())
+}
+
+/// What a query matches, to highlight it in the texts it was searched in.
+/// It is made once per query, so that a regex is compiled once rather than
+/// for every row drawn.
+pub enum Highlight {
+    /// Nothing, for an empty query or an invalid regex.
+    Nothing,
+    /// The characters a fuzzy search for the query matches.
+    Fuzzy(String),
+    /// Every match of a substring or regex search.
+    Regex(Regex),
+}
+
+impl Highlight {
+    /// Highlights what `query` matches when searched for in `mode`.
+    pub fn new(query: &str, mode: SearchMode) -> Highlight {
+        if query.is_empty() {
+            return Highlight::Nothing;
+        }
+        let pattern = match mode {
+            SearchMode::Fuzzy => return Highlight::Fuzzy(query.to_string()),
+            SearchMode::Substring => regex::escape(query),
+            SearchMode::Regex => query.to_string(),
+        };
+        let ignore_case = !query.chars().any(char::is_uppercase);
+        match RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .build()
+        {
+            Ok(regex) => Highlight::Regex(regex),
+            Err(_) => Highlight::Nothing,
+        }
+    }
+
+    /// Returns the parts of `text` that the query matches, as byte ranges in
+    /// order. They are found the way `filter` finds matches, except that
+    /// every match counts for substring and regex searches.
+    pub fn ranges(&self, matcher: &SkimMatcherV2, text: &str) -> Vec<Range<usize>> {
+        match self {
+            Highlight::Nothing => Vec::new(),
+            Highlight::Fuzzy(query) => {
+                // The indices are of the characters matched
+                let indices = match matcher.fuzzy_indices(text, query) {
+                    Some((_, indices)) => indices,
+                    None => return Vec::new(),
+                };
+                text.char_indices()
+                    .enumerate()
+                    .filter(|(i, _)| indices.binary_search(i).is_ok())
+                    .map(|(_, (at, c))| at..at + c.len_utf8())
+                    .collect()
+            }
+            Highlight::Regex(regex) => regex
+                .find_iter(text)
+                .map(|m| m.range())
+                .filter(|range| !range.is_empty())
+                .collect(),
+        }
+    }
+}
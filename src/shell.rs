@@ -0,0 +1,434 @@
+//! Just enough of shell syntax to tell the parts of a command apart, for
+//! highlighting it and for looking up what its words are. Commands that
+//! aren't valid shell still split into tokens, just less usefully.
+
+use std::ops::Range;
+
+/// Tools whose first argument is a subcommand, like `git push`.
+pub const SUBCOMMAND_TOOLS: &[&str] = &[
+    "apt",
+    "brew",
+    "cargo",
+    "docker",
+    "gh",
+    "git",
+    "go",
+    "helm",
+    "kubectl",
+    "npm",
+    "pip",
+    "pnpm",
+    "podman",
+    "systemctl",
+    "terraform",
+    "yarn",
+];
+
+// Commands that run the command after them, like `sudo`
+const PREFIXES: &[&str] = &[
+    "builtin", "command", "doas", "env", "exec", "nice", "nohup", "sudo", "time",
+];
+
+/// What a part of a command is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    /// The program run, like `git`.
+    Command,
+    /// The first argument of a tool in `SUBCOMMAND_TOOLS`, like `push` in `git push`.
+    Subcommand,
+    /// An option, like `-la` or the `--name=` of `--name=value`.
+    Flag,
+    /// A quoted string.
+    String,
+    /// A variable, or the name of one being assigned.
+    Variable,
+    /// Pipes, redirections, `&&`, `;` and the like.
+    Operator,
+    /// A comment, from `#` to the end.
+    Comment,
+}
+
+/// A part of a command, as a byte range of it. Plain arguments aren't tokens.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Token {
+    /// Where the token is in the command.
+    pub range: Range<usize>,
+    /// What the token is.
+    pub kind: Kind,
+}
+
+/// Splits `command` into tokens, in the order they appear in it.
+pub fn tokens(command: &str) -> Vec<Token> {
+    Tokenizer {
+        command,
+        bytes: command.as_bytes(),
+        at: 0,
+        tokens: Vec::new(),
+        expect_command: true,
+        program: "",
+        arguments: 0,
+    }
+    .run()
+}
+
+/// The program `command` runs and its subcommand if it has one, like `git`
+/// and `commit`, leaving out what runs it like `sudo`. The program of a
+/// pipeline is the first one.
+pub fn program(command: &str) -> Option<(&str, Option<&str>)> {
+    let tokens = tokens(command);
+    let at = tokens.iter().position(|token| {
+        token.kind == Kind::Command && !PREFIXES.contains(&&command[token.range.clone()])
+    })?;
+    let subcommand = tokens
+        .get(at + 1)
+        .filter(|token| token.kind == Kind::Subcommand)
+        .map(|token| &command[token.range.clone()]);
+    Some((&command[tokens[at].range.clone()], subcommand))
+}
+
+/// Whether `word` looks like a subcommand rather than a file or a value.
+pub fn is_subcommand(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_lowercase())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Whether `word` assigns a variable, like `FOO=bar`.
+pub fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+fn is_operator(byte: u8) -> bool {
+    matches!(byte, b'|' | b'&' | b';' | b'<' | b'>' | b'(' | b')' | b'`')
+}
+
+fn ends_word(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || is_operator(byte)
+}
+
+// Whether a `$` followed by `byte` is a variable
+fn names_variable(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"{_?!#$@*-".contains(&byte)
+}
+
+struct Tokenizer<'a> {
+    command: &'a str,
+    bytes: &'a [u8],
+    at: usize,
+    tokens: Vec<Token>,
+    // Whether the next word is a command rather than an argument
+    expect_command: bool,
+    // The command the words are arguments of, and how many came so far
+    program: &'a str,
+    arguments: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn run(mut self) -> Vec<Token> {
+        while let Some(&byte) = self.bytes.get(self.at) {
+            match byte {
+                // A line continuation
+                b'\\' if self.peek(1) == Some(b'\n') => self.at += 2,
+                b'\n' => {
+                    self.at += 1;
+                    self.expect_command = true;
+                }
+                _ if byte.is_ascii_whitespace() => self.at += 1,
+                b'#' => {
+                    let end = self.command[self.at..]
+                        .find('\n')
+                        .map_or(self.command.len(), |end| self.at + end);
+                    self.push(self.at..end, Kind::Comment);
+                    self.at = end;
+                }
+                b'$' if self.peek(1) == Some(b'(') => {
+                    self.push(self.at..self.at + 2, Kind::Operator);
+                    self.at += 2;
+                    self.expect_command = true;
+                }
+                _ if is_operator(byte) || self.at_redirection() => self.operator(),
+                _ => self.word(),
+            }
+        }
+        self.tokens
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.at + offset).copied()
+    }
+
+    fn push(&mut self, range: Range<usize>, kind: Kind) {
+        if !range.is_empty() {
+            self.tokens.push(Token { range, kind });
+        }
+    }
+
+    // Whether a file descriptor is redirected here, like the `2>` of `2>&1`
+    fn at_redirection(&self) -> bool {
+        let digits = self.bytes[self.at..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        digits > 0 && matches!(self.peek(digits), Some(b'<' | b'>'))
+    }
+
+    fn operator(&mut self) {
+        let start = self.at;
+        while self.peek(0).is_some_and(|b| b.is_ascii_digit()) {
+            self.at += 1;
+        }
+        while self.peek(0).is_some_and(is_operator) {
+            self.at += 1;
+        }
+        let operator = &self.command[start..self.at];
+        // What `>&` redirects to, like the `1` of `2>&1`
+        if operator.ends_with(">&") || operator.ends_with("<&") {
+            while self
+                .peek(0)
+                .is_some_and(|b| b.is_ascii_digit() || b == b'-')
+            {
+                self.at += 1;
+            }
+        }
+        self.push(start..self.at, Kind::Operator);
+        // A redirection is followed by a file, anything else by a command
+        let redirection = operator.contains(['<', '>']);
+        if operator.contains(['|', ';', '(', '`']) || operator.contains('&') && !redirection {
+            self.expect_command = true;
+        }
+    }
+
+    fn word(&mut self) {
+        let start = self.at;
+        // The unquoted start of the word, which tells what kind of word it is
+        let plain = self.bytes[start..]
+            .iter()
+            .position(|&b| ends_word(b) || matches!(b, b'\'' | b'"' | b'$' | b'\\'))
+            .map_or(self.command.len(), |end| start + end);
+        let text = &self.command[start..plain];
+        let whole = self.bytes.get(plain).is_none_or(|&b| ends_word(b));
+
+        let mut kind = if self.expect_command {
+            if is_assignment(text) {
+                let name = start + text.find('=').unwrap_or_default();
+                self.push(start..name, Kind::Variable);
+                self.at = name + 1;
+                self.rest_of_word(None);
+                return;
+            }
+            if text.starts_with('-') && PREFIXES.contains(&self.program) {
+                Some(Kind::Flag)
+            } else {
+                self.expect_command = whole && PREFIXES.contains(&text);
+                self.program = text;
+                self.arguments = 0;
+                Some(Kind::Command)
+            }
+        } else {
+            self.arguments += 1;
+            if text.len() > 1 && text.starts_with('-') {
+                Some(Kind::Flag)
+            } else if self.arguments == 1
+                && whole
+                && SUBCOMMAND_TOOLS.contains(&self.program)
+                && is_subcommand(text)
+            {
+                Some(Kind::Subcommand)
+            } else {
+                None
+            }
+        };
+        // Only the name of `--name=value` is the flag
+        if kind == Some(Kind::Flag) {
+            if let Some(eq) = text.find('=') {
+                self.push(start..start + eq + 1, Kind::Flag);
+                self.at = start + eq + 1;
+                kind = None;
+            }
+        }
+        self.rest_of_word(kind);
+    }
+
+    /// Reads up to the end of the current word, giving its unquoted parts `kind`.
+    fn rest_of_word(&mut self, kind: Option<Kind>) {
+        let mut plain = self.at;
+        while let Some(byte) = self.peek(0) {
+            if ends_word(byte) || byte == b'$' && self.peek(1) == Some(b'(') {
+                break;
+            }
+            match byte {
+                b'\'' | b'"' | b'$' => {
+                    if let Some(kind) = kind {
+                        self.push(plain..self.at, kind);
+                    }
+                    match byte {
+                        b'\'' => self.single_quoted(self.at),
+                        b'"' => self.double_quoted(),
+                        _ if self.peek(1) == Some(b'\'') => {
+                            // An ANSI-C string, like `$'\n'`
+                            let start = self.at;
+                            self.at += 1;
+                            self.single_quoted(start);
+                        }
+                        _ => self.variable(),
+                    }
+                    plain = self.at;
+                }
+                b'\\' => self.escape(),
+                _ => self.at += 1,
+            }
+        }
+        if let Some(kind) = kind {
+            self.push(plain..self.at, kind);
+        }
+    }
+
+    // Skips a backslash and the character it escapes
+    fn escape(&mut self) {
+        self.at += 1;
+        if let Some(c) = self.command[self.at..].chars().next() {
+            self.at += c.len_utf8();
+        }
+    }
+
+    fn single_quoted(&mut self, start: usize) {
+        let end = self.command[self.at + 1..]
+            .find('\'')
+            .map_or(self.command.len(), |end| self.at + 1 + end + 1);
+        self.push(start..end, Kind::String);
+        self.at = end;
+    }
+
+    // Variables in double quotes are tokens of their own
+    fn double_quoted(&mut self) {
+        let mut start = self.at;
+        self.at += 1;
+        while let Some(byte) = self.peek(0) {
+            match byte {
+                b'"' => {
+                    self.at += 1;
+                    break;
+                }
+                b'\\' => self.escape(),
+                b'$' if self.peek(1).is_some_and(names_variable) => {
+                    self.push(start..self.at, Kind::String);
+                    self.variable();
+                    start = self.at;
+                }
+                _ => self.at += 1,
+            }
+        }
+        self.push(start..self.at, Kind::String);
+    }
+
+    // A `$` with what follows it, which is only a variable when it names one
+    fn variable(&mut self) {
+        let start = self.at;
+        self.at += 1;
+        match self.peek(0) {
+            Some(b) if !names_variable(b) => return,
+            Some(b'{') => {
+                self.at = self.command[self.at..]
+                    .find('}')
+                    .map_or(self.command.len(), |end| self.at + end + 1);
+            }
+            Some(b) if b.is_ascii_alphabetic() || b == b'_' => {
+                while self
+                    .peek(0)
+                    .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_')
+                {
+                    self.at += 1;
+                }
+            }
+            Some(_) => self.at += 1,
+            None => return,
+        }
+        self.push(start..self.at, Kind::Variable);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn kinds(command: &str) -> Vec<(&str, Kind)> {
+        tokens(command)
+            .into_iter()
+            .map(|token| (&command[token.range], token.kind))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn tokens_are_in_order_and_on_character_boundaries(command in "[ -~\n\t√é]{0,40}") {
+            let mut end = 0;
+            for token in tokens(&command) {
+                prop_assert!(token.range.start >= end && token.range.start < token.range.end);
+                prop_assert!(command.is_char_boundary(token.range.start));
+                prop_assert!(command.is_char_boundary(token.range.end));
+                end = token.range.end;
+            }
+            prop_assert!(end <= command.len());
+        }
+    }
+
+    #[test]
+    fn finds_the_program_behind_prefixes() {
+        assert_eq!(
+            program("sudo -E git commit -m x"),
+            Some(("git", Some("commit")))
+        );
+        assert_eq!(
+            program("FOO=1 docker run nginx | less"),
+            Some(("docker", Some("run")))
+        );
+        assert_eq!(program("ls -la"), Some(("ls", None)));
+        assert_eq!(program("# a comment"), None);
+    }
+
+    #[test]
+    fn tells_the_parts_of_a_pipeline_apart() {
+        use Kind::*;
+        assert_eq!(
+            kinds(
+                r#"FOO=1 sudo git commit -m "fix $USER's bug" --author='me' 2>&1 | grep -v x >> log # done"#
+            ),
+            [
+                ("FOO", Variable),
+                ("sudo", Command),
+                ("git", Command),
+                ("commit", Subcommand),
+                ("-m", Flag),
+                ("\"fix ", String),
+                ("$USER", Variable),
+                ("'s bug\"", String),
+                ("--author=", Flag),
+                ("'me'", String),
+                ("2>&1", Operator),
+                ("|", Operator),
+                ("grep", Command),
+                ("-v", Flag),
+                (">>", Operator),
+                ("# done", Comment),
+            ]
+        );
+        assert_eq!(
+            kinds("echo $(date +%s)\\\n  && ls"),
+            [
+                ("echo", Command),
+                ("$(", Operator),
+                ("date", Command),
+                (")", Operator),
+                ("&&", Operator),
+                ("ls", Command),
+            ]
+        );
+    }
+}
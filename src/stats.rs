@@ -0,0 +1,365 @@
+//! `hui stats` and the statistics view: what is run, how often and when.
+
+use crate::history::Command;
+use crate::ignore::Ignore;
+use crate::shell::{is_assignment, is_subcommand, SUBCOMMAND_TOOLS};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Timelike};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// How many entries each of the top lists keeps
+const TOP: usize = 10;
+// How many days back the daily counts go
+const DAYS: i64 = 30;
+// Failure rates are only shown for binaries run at least this many times with a known exit code
+const MIN_RUNS: usize = 3;
+
+/// Statistics about every run in the history, not only the deduplicated
+/// entries, so that repeated commands count as often as they were run.
+/// Commands hidden by the ignore rules are left out. Everything about time
+/// is in the local timezone.
+#[derive(Serialize)]
+pub struct Stats {
+    /// How many times commands were run.
+    pub runs: usize,
+    /// How many different commands were run.
+    pub unique: usize,
+    /// Runs left out because the ignore rules hide them.
+    pub ignored: usize,
+    /// The commands run most often.
+    pub top_commands: Vec<Count>,
+    /// Counted by the first word of each command, after `sudo` and variable assignments.
+    pub top_binaries: Vec<Count>,
+    /// The subcommands of tools like git run most often, like `git push`.
+    pub top_subcommands: Vec<Count>,
+    /// Runs per hour of the day, from midnight.
+    pub hours: [usize; 24],
+    /// Runs per weekday and hour, from Monday.
+    pub heatmap: [[usize; 24]; 7],
+    /// Runs per day over the last 30 days, oldest first.
+    pub days: Vec<Day>,
+    /// The runs that took longest, longest first.
+    pub longest: Vec<Timed>,
+    /// The binaries that fail most often, highest failure rate first.
+    pub failure_rates: Vec<Failures>,
+}
+
+/// How many times something was run.
+#[derive(Serialize)]
+pub struct Count {
+    /// The command, binary or subcommand.
+    pub name: String,
+    /// How many times it was run.
+    pub count: usize,
+}
+
+/// How many times commands were run on a day.
+#[derive(Serialize)]
+pub struct Day {
+    /// The day.
+    pub date: NaiveDate,
+    /// How many runs there were that day.
+    pub count: usize,
+}
+
+/// A run and how long it took.
+#[derive(Serialize)]
+pub struct Timed {
+    /// The command run.
+    pub command: String,
+    /// In milliseconds.
+    pub duration: u64,
+}
+
+/// How often a binary failed, i.e. exited with a status other than 0.
+#[derive(Serialize)]
+pub struct Failures {
+    /// The first word of the commands.
+    pub binary: String,
+    /// Runs with a known exit code.
+    pub runs: usize,
+    /// Runs that failed.
+    pub failed: usize,
+}
+
+impl Failures {
+    /// The share of the runs that failed, from 0 to 1.
+    pub fn rate(&self) -> f64 {
+        self.failed as f64 / self.runs as f64
+    }
+}
+
+impl Stats {
+    /// Computes the statistics of the commands read from every source, as of `now`.
+    pub fn new(histories: &[Vec<Command>], ignore: &Ignore, now: DateTime<Local>) -> Stats {
+        let (runs, ignored): (Vec<&Command>, Vec<&Command>) = histories
+            .iter()
+            .flatten()
+            .partition(|run| !ignore.is_ignored(&run.command));
+
+        let mut commands: HashMap<&str, usize> = HashMap::new();
+        let mut binaries: HashMap<&str, usize> = HashMap::new();
+        let mut subcommands: HashMap<String, usize> = HashMap::new();
+        let mut hours = [0; 24];
+        let mut heatmap = [[0; 24]; 7];
+        let today = now.date_naive();
+        let first_day = today - Duration::days(DAYS - 1);
+        let mut days = vec![0; DAYS as usize];
+        let mut longest: HashMap<&str, u64> = HashMap::new();
+        let mut exits: HashMap<&str, (usize, usize)> = HashMap::new();
+
+        for run in &runs {
+            *commands.entry(run.command.as_str()).or_default() += 1;
+            let words = words(&run.command);
+            if let Some(binary) = words.first() {
+                *binaries.entry(binary).or_default() += 1;
+                if let Some(exit) = run.context.exit {
+                    let (runs, failed) = exits.entry(binary).or_default();
+                    *runs += 1;
+                    if exit != 0 {
+                        *failed += 1;
+                    }
+                }
+            }
+            if let [binary, subcommand, ..] = words[..] {
+                if SUBCOMMAND_TOOLS.contains(&binary) && is_subcommand(subcommand) {
+                    *subcommands
+                        .entry(format!("{} {}", binary, subcommand))
+                        .or_default() += 1;
+                }
+            }
+            if let Some(duration) = run.context.duration {
+                let longest = longest.entry(run.command.as_str()).or_default();
+                *longest = (*longest).max(duration);
+            }
+            let time = run
+                .timestamp
+                .and_then(|t| Local.timestamp_opt(t as i64, 0).single());
+            if let Some(time) = time {
+                let hour = time.hour() as usize;
+                hours[hour] += 1;
+                heatmap[time.weekday().num_days_from_monday() as usize][hour] += 1;
+                let day = (time.date_naive() - first_day).num_days();
+                if (0..DAYS).contains(&day) {
+                    days[day as usize] += 1;
+                }
+            }
+        }
+
+        let mut longest: Vec<Timed> = longest
+            .into_iter()
+            .map(|(command, duration)| Timed {
+                command: command.to_string(),
+                duration,
+            })
+            .collect();
+        longest.sort_by(|a, b| b.duration.cmp(&a.duration).then(a.command.cmp(&b.command)));
+        longest.truncate(TOP);
+
+        let mut failure_rates: Vec<Failures> = exits
+            .into_iter()
+            .filter(|(_, (runs, failed))| *runs >= MIN_RUNS && *failed > 0)
+            .map(|(binary, (runs, failed))| Failures {
+                binary: binary.to_string(),
+                runs,
+                failed,
+            })
+            .collect();
+        failure_rates.sort_by(|a, b| {
+            b.rate()
+                .total_cmp(&a.rate())
+                .then(b.runs.cmp(&a.runs))
+                .then(a.binary.cmp(&b.binary))
+        });
+        failure_rates.truncate(TOP);
+
+        Stats {
+            runs: runs.len(),
+            unique: commands.len(),
+            ignored: ignored.len(),
+            top_commands: top(commands),
+            top_binaries: top(binaries),
+            top_subcommands: top(subcommands),
+            hours,
+            heatmap,
+            days: days
+                .into_iter()
+                .enumerate()
+                .map(|(i, count)| Day {
+                    date: first_day + Duration::days(i as i64),
+                    count,
+                })
+                .collect(),
+            longest,
+            failure_rates,
+        }
+    }
+
+    /// Whether any run had a timestamp, without which there is nothing to show per hour or day.
+    pub fn has_times(&self) -> bool {
+        self.hours.iter().any(|count| *count > 0)
+    }
+
+    /// Writes the statistics as JSON, for `hui stats --json`.
+    pub fn write_json(&self, out: &mut dyn Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, self)?;
+        writeln!(out)
+    }
+
+    /// Writes the statistics as text, with bars for the counts.
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{} commands run, {} of them unique",
+            self.runs, self.unique
+        )?;
+        if self.ignored > 0 {
+            writeln!(out, "{} more hidden by the ignore rules", self.ignored)?;
+        }
+
+        for (title, counts) in [
+            ("Top commands", &self.top_commands),
+            ("Top binaries", &self.top_binaries),
+            ("Top subcommands", &self.top_subcommands),
+        ] {
+            writeln!(out, "\n{}", title)?;
+            let max = counts.first().map_or(0, |count| count.count);
+            let width = counts
+                .iter()
+                .map(|c| one_line(&c.name).chars().count())
+                .max();
+            for count in counts {
+                writeln!(
+                    out,
+                    "  {:<width$}  {:>6}  {}",
+                    one_line(&count.name),
+                    count.count,
+                    bar(count.count, max, 30),
+                    width = width.unwrap_or(0).min(50)
+                )?;
+            }
+        }
+
+        if self.has_times() {
+            writeln!(out, "\nRuns per hour")?;
+            let max = self.hours.iter().max().copied().unwrap_or(0);
+            for (hour, count) in self.hours.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  {:02}:00  {:>6}  {}",
+                    hour,
+                    count,
+                    bar(*count, max, 30)
+                )?;
+            }
+
+            writeln!(out, "\nRuns per weekday and hour")?;
+            writeln!(out, "       0     6     12    18")?;
+            let max = self.heatmap.iter().flatten().max().copied().unwrap_or(0);
+            for (day, hours) in WEEKDAYS.iter().zip(&self.heatmap) {
+                let cells: String = hours.iter().map(|count| shade(*count, max)).collect();
+                writeln!(out, "  {}  {}", day, cells)?;
+            }
+
+            writeln!(out, "\nRuns per day, last {} days", DAYS)?;
+            let max = self.days.iter().map(|day| day.count).max().unwrap_or(0);
+            for day in &self.days {
+                writeln!(
+                    out,
+                    "  {}  {:>6}  {}",
+                    day.date,
+                    day.count,
+                    bar(day.count, max, 30)
+                )?;
+            }
+        }
+
+        if !self.longest.is_empty() {
+            writeln!(out, "\nLongest running")?;
+            for timed in &self.longest {
+                writeln!(
+                    out,
+                    "  {:>9}  {}",
+                    duration(timed.duration),
+                    one_line(&timed.command)
+                )?;
+            }
+        }
+
+        if !self.failure_rates.is_empty() {
+            writeln!(out, "\nFailure rates")?;
+            for failures in &self.failure_rates {
+                writeln!(
+                    out,
+                    "  {:>5.1}%  {} ({} of {} runs)",
+                    failures.rate() * 100.0,
+                    failures.binary,
+                    failures.failed,
+                    failures.runs
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The names of the weekdays, from Monday, as in `heatmap`.
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// The words of a command, leaving out `sudo` and variable assignments before it.
+fn words(command: &str) -> Vec<&str> {
+    let mut words: Vec<&str> = command.split_whitespace().collect();
+    let skip = words
+        .iter()
+        .take_while(|word| **word == "sudo" || is_assignment(word))
+        .count();
+    words.drain(..skip);
+    words
+}
+
+/// The `TOP` highest counts, highest first, then by name.
+fn top<K: ToString>(counts: HashMap<K, usize>) -> Vec<Count> {
+    let mut counts: Vec<Count> = counts
+        .into_iter()
+        .map(|(name, count)| Count {
+            name: name.to_string(),
+            count,
+        })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+    counts.truncate(TOP);
+    counts
+}
+
+/// Shows a multiline command on one line.
+pub fn one_line(command: &str) -> String {
+    command.replace('\n', " ⏎ ")
+}
+
+fn bar(count: usize, max: usize, width: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    "█".repeat((count * width).div_ceil(max))
+}
+
+/// A character for how busy an hour was, from blank to a full block.
+pub fn shade(count: usize, max: usize) -> char {
+    const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+    if count == 0 || max == 0 {
+        return SHADES[0];
+    }
+    SHADES[(count * 4).div_ceil(max).min(4)]
+}
+
+/// Formats milliseconds, e.g. `850 ms`, `12.5 s` or `3 min 20 s`.
+pub fn duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{} ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1} s", ms as f64 / 1000.0)
+    } else {
+        format!("{} min {} s", ms / 60_000, ms % 60_000 / 1000)
+    }
+}
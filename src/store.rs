@@ -0,0 +1,481 @@
+//! hui's data directory, and the data files kept in it, which may be
+//! encrypted.
+
+use crate::crypto::{self, Key};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// What encrypted data files start with. Any other data file is plain JSON.
+const ENCRYPTED: &[u8] = b"hui-encrypted\n";
+
+/// The data file with the salt `$HUI_PASSPHRASE` is turned into a key with,
+/// the one data file that is never encrypted.
+pub const SALT_FILE: &str = "salt.json";
+
+/// The key for the data files, derived once, since deriving one from a
+/// passphrase takes a while on purpose.
+static KEY: OnceLock<Result<Option<Key>, (io::ErrorKind, String)>> = OnceLock::new();
+
+/// The encrypted data files decrypted so far, so that each of them is only
+/// decrypted once.
+static DECRYPTED: Mutex<BTreeMap<PathBuf, Decrypted>> = Mutex::new(BTreeMap::new());
+
+/// The contents of an encrypted data file, with the modification time and
+/// length the file had, to tell when it changed.
+struct Decrypted {
+    modified: Option<SystemTime>,
+    len: u64,
+    contents: Vec<u8>,
+}
+
+/// Directory where hui keeps its own data, such as saved templates.
+///
+/// This is `$HUI_DATA_DIR` if set, otherwise `$XDG_DATA_HOME/hui`,
+/// falling back to `~/.local/share/hui`.
+pub fn data_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("HUI_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir).join("hui"));
+    }
+    match env::home_dir() {
+        Some(home) => Ok(home.join(".local").join("share").join("hui")),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "couldn't find a data directory, set $HUI_DATA_DIR",
+        )),
+    }
+}
+
+/// Where the key the data files are encrypted with comes from.
+pub enum KeySource {
+    /// A key file, `$HUI_KEY_FILE`.
+    File(PathBuf),
+    /// A passphrase, `$HUI_PASSPHRASE`.
+    Passphrase(String),
+}
+
+impl KeySource {
+    /// `$HUI_KEY_FILE`, or else `$HUI_PASSPHRASE`. The data files aren't
+    /// encrypted when neither is set.
+    pub fn from_env() -> Option<KeySource> {
+        env::var_os("HUI_KEY_FILE")
+            .filter(|path| !path.is_empty())
+            .map(|path| KeySource::File(PathBuf::from(path)))
+            .or_else(KeySource::passphrase)
+    }
+
+    fn passphrase() -> Option<KeySource> {
+        env::var("HUI_PASSPHRASE")
+            .ok()
+            .filter(|passphrase| !passphrase.is_empty())
+            .map(KeySource::Passphrase)
+    }
+
+    fn key(&self) -> io::Result<Key> {
+        match self {
+            KeySource::File(path) => Key::read(path).map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "{}: no such key file, `hui key rotate` creates it",
+                        path.display()
+                    ),
+                ),
+                _ => err,
+            }),
+            KeySource::Passphrase(passphrase) => {
+                let dir = data_dir()?;
+                fs::create_dir_all(&dir)?;
+                Key::from_passphrase(passphrase, &crypto::read_salt(&dir.join(SALT_FILE))?)
+            }
+        }
+    }
+}
+
+fn key() -> io::Result<Option<Key>> {
+    KEY.get_or_init(|| {
+        KeySource::from_env()
+            .map(|source| source.key())
+            .transpose()
+            .map_err(|err| (err.kind(), err.to_string()))
+    })
+    .clone()
+    .map_err(|(kind, message)| io::Error::new(kind, message))
+}
+
+/// Whether a data file is encrypted while neither `$HUI_KEY_FILE` nor
+/// `$HUI_PASSPHRASE` is set, so that the passphrase has to be asked for.
+pub fn needs_passphrase() -> bool {
+    if KeySource::from_env().is_some() {
+        return false;
+    }
+    data_dir().is_ok_and(|dir| any_encrypted(&dir))
+}
+
+/// Whether any file in `dir` is an encrypted data file.
+fn any_encrypted(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.filter_map(Result::ok).any(|entry| {
+        let mut start = [0; ENCRYPTED.len()];
+        fs::File::open(entry.path())
+            .and_then(|mut file| file.read_exact(&mut start))
+            .is_ok_and(|()| start == ENCRYPTED)
+    })
+}
+
+/// Decrypts the data files with `passphrase`, as if `$HUI_PASSPHRASE` were
+/// set to it. Only has an effect before the first data file is read.
+pub fn use_passphrase(passphrase: String) {
+    KEY.get_or_init(|| {
+        KeySource::Passphrase(passphrase)
+            .key()
+            .map(Some)
+            .map_err(|err| (err.kind(), err.to_string()))
+    });
+}
+
+/// Reads the data file at `path`, decrypting it when it is encrypted.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let metadata = fs::metadata(path)?;
+    let (modified, len) = (metadata.modified().ok(), metadata.len());
+    let decrypted = || DECRYPTED.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(cached) = decrypted().get(path) {
+        if cached.modified == modified && cached.len == len {
+            return Ok(cached.contents.clone());
+        }
+    }
+    let contents = fs::read(path)?;
+    if !contents.starts_with(ENCRYPTED) {
+        return Ok(contents);
+    }
+    let contents = decrypt(path, &contents, key()?.as_ref())?;
+    let cached = Decrypted {
+        modified,
+        len,
+        contents: contents.clone(),
+    };
+    decrypted().insert(path.to_path_buf(), cached);
+    Ok(contents)
+}
+
+fn decrypt(path: &Path, contents: &[u8], key: Option<&Key>) -> io::Result<Vec<u8>> {
+    let Some(encrypted) = contents.strip_prefix(ENCRYPTED) else {
+        return Ok(contents.to_vec());
+    };
+    let Some(key) = key else {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{}: encrypted, set $HUI_PASSPHRASE or $HUI_KEY_FILE",
+                path.display()
+            ),
+        ));
+    };
+    key.decrypt(encrypted)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+}
+
+fn encrypt(contents: Vec<u8>, key: Option<&Key>) -> Vec<u8> {
+    match key {
+        Some(key) => [ENCRYPTED, &key.encrypt(&contents)].concat(),
+        None => contents,
+    }
+}
+
+/// Reads the data file `name`, or returns the default value if it doesn't exist yet.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> io::Result<T> {
+    let path = data_dir()?.join(name);
+    match read(&path) {
+        Ok(contents) => serde_json::from_slice(&contents).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads the data file `name` of rules with regexes in them, and compiles
+/// them with `compile`. The regexes are given by `regexes`, so that one that
+/// is invalid can be named on its own: the errors of the regex crate span
+/// several lines.
+pub fn load_rules<R: DeserializeOwned + Default, T>(
+    name: &str,
+    regexes: impl Fn(&R) -> &[String],
+    compile: impl FnOnce(R) -> Result<T, regex::Error>,
+) -> io::Result<T> {
+    let rules: R = load(name)?;
+    if let Some(pattern) = regexes(&rules).iter().find(|r| Regex::new(r).is_err()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: invalid regex {}", name, pattern),
+        ));
+    }
+    compile(rules).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Writes the data file `name`, creating the data directory if needed. It is
+/// encrypted when `$HUI_KEY_FILE` or `$HUI_PASSPHRASE` is set.
+pub fn save<T: Serialize>(name: &str, value: &T) -> io::Result<()> {
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir)?;
+    write(
+        &dir,
+        name,
+        serde_json::to_vec_pretty(value)?,
+        key()?.as_ref(),
+    )
+}
+
+/// Writes `contents` to the data file `name` in `dir`, encrypted with `key`.
+/// Without a key, it refuses to when other data files are encrypted, rather
+/// than leave it in the clear next to them.
+fn write(dir: &Path, name: &str, contents: Vec<u8>, key: Option<&Key>) -> io::Result<()> {
+    let path = dir.join(name);
+    if key.is_none() && any_encrypted(dir) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{}: the other data files are encrypted, set $HUI_PASSPHRASE or $HUI_KEY_FILE",
+                path.display()
+            ),
+        ));
+    }
+    // Write to a temporary file first so a crash can't leave a half-written file behind
+    fs::write(tmp(&path), encrypt(contents, key))?;
+    fs::rename(tmp(&path), path)
+}
+
+/// Encrypts every data file with a new key, returning how many there are.
+/// Files that aren't encrypted yet are encrypted too, so this is also how
+/// encrypting the data files is started.
+///
+/// With `$HUI_KEY_FILE`, the new key is random and replaces the one in the
+/// key file, which is created if it doesn't exist; the files are then
+/// decrypted with `$HUI_PASSPHRASE`, if set. With `$HUI_PASSPHRASE` alone,
+/// the new key is derived from `new_passphrase`, or the same passphrase
+/// again, with a new salt.
+pub fn rotate(new_passphrase: Option<&str>) -> io::Result<usize> {
+    let Some(source) = KeySource::from_env() else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "set $HUI_PASSPHRASE or $HUI_KEY_FILE to encrypt the data files",
+        ));
+    };
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir)?;
+    let mut names: Vec<String> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".json") && !name.starts_with('.') && name != SALT_FILE)
+        .collect();
+    names.sort();
+    // Everything is decrypted before anything is written, so that a file
+    // that can't be read leaves them all as they were
+    let encrypted = names
+        .iter()
+        .map(|name| fs::read(dir.join(name)))
+        .collect::<io::Result<Vec<_>>>()?;
+    let old = match &source {
+        _ if !encrypted
+            .iter()
+            .any(|contents| contents.starts_with(ENCRYPTED)) =>
+        {
+            None
+        }
+        // Moving from a passphrase to a key file
+        KeySource::File(path) if !path.exists() => match KeySource::passphrase() {
+            Some(passphrase) => Some(passphrase.key()?),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "{}: no such key file, set $HUI_PASSPHRASE as well to move from a passphrase to it",
+                        path.display()
+                    ),
+                ))
+            }
+        },
+        _ => Some(source.key()?),
+    };
+    let contents = names
+        .iter()
+        .zip(&encrypted)
+        .map(|(name, contents)| decrypt(&dir.join(name), contents, old.as_ref()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    // The new key, and the key file or salt file it comes from
+    let (key, path) = match source {
+        KeySource::File(path) => {
+            let key = Key::generate();
+            let _ = fs::remove_file(tmp(&path));
+            key.write(&tmp(&path))?;
+            (key, path)
+        }
+        KeySource::Passphrase(passphrase) => {
+            let salt = crypto::generate_salt();
+            let passphrase = new_passphrase.unwrap_or(&passphrase);
+            let key = Key::from_passphrase(passphrase, &crypto::decode_salt(&salt)?)?;
+            let path = dir.join(SALT_FILE);
+            crypto::write_salt(&tmp(&path), &salt)?;
+            (key, path)
+        }
+    };
+    let reencrypted = contents
+        .into_iter()
+        .map(|contents| encrypt(contents, Some(&key)))
+        .collect();
+    let commit = || fs::rename(tmp(&path), &path);
+    if let Err(err) = replace(&dir, &names, &encrypted, reencrypted, commit) {
+        let _ = fs::remove_file(tmp(&path));
+        return Err(err);
+    }
+    Ok(names.len())
+}
+
+/// The temporary file `path` is written to before it is renamed into place.
+fn tmp(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// Replaces the data files `names` in `dir`, whose contents are `old`, with
+/// `new`, and then calls `commit` to put the new key in place. The old key
+/// stays where it is until every file has been replaced, and when one can't
+/// be, those replaced so far are put back, so that every file can still be
+/// decrypted with the old key.
+fn replace(
+    dir: &Path,
+    names: &[String],
+    old: &[Vec<u8>],
+    new: Vec<Vec<u8>>,
+    commit: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    let result = names
+        .iter()
+        .zip(new)
+        .try_for_each(|(name, contents)| fs::write(tmp(&dir.join(name)), contents));
+    let mut replaced = 0;
+    let result = result.and_then(|()| {
+        for name in names {
+            fs::rename(tmp(&dir.join(name)), dir.join(name))?;
+            replaced += 1;
+        }
+        commit()
+    });
+    if result.is_err() {
+        for name in &names[replaced..] {
+            let _ = fs::remove_file(tmp(&dir.join(name)));
+        }
+        for (name, contents) in names.iter().zip(old).take(replaced) {
+            let path = dir.join(name);
+            let _ = fs::write(tmp(&path), contents).and_then(|()| fs::rename(tmp(&path), path));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failing_to_rotate_keeps_the_old_key() {
+        let dir = env::temp_dir().join(format!("hui-test-rotate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (old_key, new_key) = (Key::generate(), Key::generate());
+        let names: Vec<String> = ["a.json", "b.json", "c.json"].map(String::from).to_vec();
+        let plain = |name: &str| format!("[\"{}\"]", name).into_bytes();
+        let old: Vec<Vec<u8>> = names
+            .iter()
+            .map(|name| encrypt(plain(name), Some(&old_key)))
+            .collect();
+        let new: Vec<Vec<u8>> = names
+            .iter()
+            .map(|name| encrypt(plain(name), Some(&new_key)))
+            .collect();
+        for (name, contents) in names.iter().zip(&old) {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let readable_with = |key: &Key, names: &[String]| {
+            names.iter().all(|name| {
+                let path = dir.join(name);
+                decrypt(&path, &fs::read(&path).unwrap(), Some(key)).ok() == Some(plain(name))
+            })
+        };
+
+        // A file can't be renamed over a directory, so replacing b.json fails
+        // after a.json has been replaced
+        fs::remove_file(dir.join("b.json")).unwrap();
+        fs::create_dir(dir.join("b.json")).unwrap();
+        let committed = std::cell::Cell::new(false);
+        let commit = || {
+            committed.set(true);
+            Ok(())
+        };
+        assert!(replace(&dir, &names, &old, new.clone(), commit).is_err());
+        assert!(!committed.get());
+        assert!(readable_with(
+            &old_key,
+            &[names[0].clone(), names[2].clone()]
+        ));
+        assert!(names.iter().all(|name| !tmp(&dir.join(name)).exists()));
+
+        fs::remove_dir(dir.join("b.json")).unwrap();
+        fs::write(dir.join("b.json"), &old[1]).unwrap();
+        replace(&dir, &names, &old, new, commit).unwrap();
+        assert!(committed.get());
+        assert!(readable_with(&new_key, &names));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn nothing_is_saved_in_the_clear_next_to_encrypted_files() {
+        let dir = env::temp_dir().join(format!("hui-test-save-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let plain = b"[\"git status\"]".to_vec();
+        write(&dir, "favorites.json", plain.clone(), None).unwrap();
+        assert_eq!(fs::read(dir.join("favorites.json")).unwrap(), plain);
+
+        let key = Key::generate();
+        write(&dir, "templates.json", plain.clone(), Some(&key)).unwrap();
+        let err = write(&dir, "sync-laptop.json", plain.clone(), None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(!dir.join("sync-laptop.json").exists());
+        assert!(!tmp(&dir.join("sync-laptop.json")).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn encrypted_data_files_need_their_key() {
+        let path = Path::new("favorites.json");
+        let key = Key::generate();
+        let plain = b"[\"git status\"]".to_vec();
+        let encrypted = encrypt(plain.clone(), Some(&key));
+        assert!(encrypted.starts_with(ENCRYPTED));
+        assert_eq!(decrypt(path, &encrypted, Some(&key)).unwrap(), plain);
+
+        let err = decrypt(path, &encrypted, None).unwrap_err();
+        assert!(err.to_string().contains("$HUI_PASSPHRASE"));
+        assert!(decrypt(path, &encrypted, Some(&Key::generate())).is_err());
+
+        // Files from before encrypting are still read, and encrypted when saved
+        assert_eq!(decrypt(path, &plain, Some(&key)).unwrap(), plain);
+        assert_eq!(encrypt(plain.clone(), None), plain);
+    }
+}
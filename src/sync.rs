@@ -0,0 +1,427 @@
+//! Sharing history between machines through a directory they already keep
+//! in sync some other way, like Syncthing, Dropbox, NFS or a git repository.
+//!
+//! Every machine appends its commands to a log of its own in the directory,
+//! `hui-<host>.log`, and reads the logs of the others. Since no two machines
+//! write the same file, whatever syncs the directory never has conflicts to
+//! resolve. Each line of a log is a command as JSON, or encrypted with
+//! `crypto` when it starts with `enc:`.
+
+use crate::crypto::{self, Key};
+use crate::history::{Command, Format, Source};
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Name of the data file the sync settings are kept in.
+pub const FILE: &str = "sync.json";
+
+/// The file in the sync directory with the salt passphrases are turned into
+/// keys with, so that every machine derives the same key.
+const SALT_FILE: &str = "hui-sync.json";
+
+const ENCRYPTED: &str = "enc:";
+
+/// What `hui sync` was last run with, so that it can be run again without
+/// repeating it.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The synced directory.
+    pub dir: Option<PathBuf>,
+    /// The name this machine was given, if not its host name.
+    pub host: Option<String>,
+    /// Whether this machine encrypts what it appends to its log.
+    pub encrypt: bool,
+    /// The key file to encrypt and decrypt with.
+    pub key_file: Option<PathBuf>,
+}
+
+/// The name of this machine: `$HUI_HOST`, or else its host name.
+pub fn hostname() -> String {
+    let name = env::var("HUI_HOST")
+        .ok()
+        .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| {
+            let output = process::Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .unwrap_or_default();
+    host_name(&name)
+}
+
+/// Makes `name` safe to put in a file name, replacing what isn't.
+pub fn host_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => "localhost".to_string(),
+        name => name.to_string(),
+    }
+}
+
+fn log_path(dir: &Path, host: &str) -> PathBuf {
+    dir.join(format!("hui-{}.log", host))
+}
+
+/// The key derived from `passphrase` for the logs in `dir`, with the salt
+/// kept there. The salt is only created with `new_salt`, on the first
+/// machine: two machines each creating one before the directory synced
+/// between them would encrypt with different keys, and skip each other's
+/// logs.
+pub fn passphrase_key(dir: &Path, passphrase: &str, new_salt: bool) -> io::Result<Key> {
+    let path = dir.join(SALT_FILE);
+    match (new_salt, path.exists()) {
+        (true, true) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{}: already exists, leave out --new-salt", path.display()),
+            ))
+        }
+        (true, false) => crypto::write_salt(&path, &crypto::generate_salt())?,
+        (false, true) => {}
+        (false, false) => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{}: no sync salt yet, pass --new-salt on the first machine and wait for it to sync to the others",
+                    path.display()
+                ),
+            ))
+        }
+    }
+    Key::from_passphrase(passphrase, &crypto::read_salt(&path)?)
+}
+
+/// Reads a log, oldest command first. A last line without a line break is
+/// left out: the rest of it hasn't been synced yet.
+pub fn read_log(path: &Path, key: Option<&Key>) -> io::Result<Vec<Command>> {
+    parse_log(path, &fs::read_to_string(path)?, key)
+}
+
+fn parse_log(path: &Path, contents: &str, key: Option<&Key>) -> io::Result<Vec<Command>> {
+    let error = |line: usize, message: &dyn std::fmt::Display| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}:{}: {}", path.display(), line + 1, message),
+        )
+    };
+    let mut commands = Vec::new();
+    for (n, line) in contents.split_inclusive('\n').enumerate() {
+        let Some(line) = line.strip_suffix('\n') else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let json = match line.strip_prefix(ENCRYPTED) {
+            Some(encrypted) => match key {
+                Some(key) => key.decrypt_line(encrypted).map_err(|err| error(n, &err))?,
+                None => {
+                    return Err(error(
+                        n,
+                        &"encrypted; set $HUI_SYNC_PASSPHRASE or pass --key-file",
+                    ))
+                }
+            },
+            None => line.as_bytes().to_vec(),
+        };
+        commands.push(serde_json::from_slice(&json).map_err(|err| error(n, &err))?);
+    }
+    Ok(commands)
+}
+
+/// Two runs are the same when they have the same command and time.
+fn run_of(command: &Command) -> (Option<u64>, &str) {
+    (command.timestamp, &command.command)
+}
+
+/// Appends the commands that aren't in the log of `host` yet to it, in
+/// their order, encrypted when `key` is given. Returns how many it appended.
+pub fn push(dir: &Path, host: &str, commands: &[Command], key: Option<&Key>) -> io::Result<usize> {
+    let path = log_path(dir, host);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let logged = parse_log(&path, &contents, key)?;
+    let mut seen: HashSet<_> = logged.iter().map(run_of).collect();
+    let mut lines = String::new();
+    let mut appended = 0;
+    for command in commands
+        .iter()
+        .filter(|command| seen.insert(run_of(command)))
+    {
+        let json = serde_json::to_string(command)?;
+        match key {
+            Some(key) => {
+                lines.push_str(ENCRYPTED);
+                lines.push_str(&key.encrypt_line(json.as_bytes()));
+            }
+            None => lines.push_str(&json),
+        }
+        lines.push('\n');
+        appended += 1;
+    }
+    if appended > 0 {
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        // Only this machine writes its log, so a partial line at its end was
+        // left by a crash, and would otherwise run into the next line
+        let complete = contents.rfind('\n').map_or(0, |end| end + 1);
+        if complete < contents.len() {
+            log.set_len(complete as u64)?;
+        }
+        log.write_all(lines.as_bytes())?;
+        log.sync_all()?;
+    }
+    Ok(appended)
+}
+
+/// Reads the logs of every host in `dir` but `host`, ordered by host name.
+///
+/// A run several machines recorded, like those of a history file they
+/// share, is kept once: not at all when it is in `local`, the history of
+/// this machine, and otherwise by the first host that has it. So every
+/// machine ends up with the same runs, whatever order they synced in.
+///
+/// A log that can't be read, because it is damaged or encrypted with another
+/// key, is returned as the error reading it, so that the other hosts are
+/// still synced.
+pub fn pull(
+    dir: &Path,
+    host: &str,
+    local: &[Command],
+    key: Option<&Key>,
+) -> io::Result<Vec<(String, io::Result<Vec<Command>>)>> {
+    let mut hosts: Vec<(String, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let other = name.strip_prefix("hui-")?.strip_suffix(".log")?;
+            Some((other.to_string(), path)).filter(|(other, _)| other != host)
+        })
+        .collect();
+    hosts.sort();
+
+    let mut seen: HashSet<(Option<u64>, String)> = local
+        .iter()
+        .map(|command| (command.timestamp, command.command.clone()))
+        .collect();
+    let mut pulled = Vec::new();
+    for (other, path) in hosts {
+        let commands = read_log(&path, key).map(|mut commands| {
+            commands.retain(|command| seen.insert((command.timestamp, command.command.clone())));
+            commands
+        });
+        pulled.push((other, commands));
+    }
+    Ok(pulled)
+}
+
+fn data_file(host: &str) -> String {
+    format!("sync-{}.json", host)
+}
+
+/// Keeps what was pulled from each host in a data file of its own, for
+/// `sources` to list.
+pub fn save(pulled: &[(String, Vec<Command>)]) -> io::Result<()> {
+    for (host, commands) in pulled {
+        store::save(&data_file(host), commands)?;
+    }
+    Ok(())
+}
+
+/// The sources for the history of every other host synced so far, labelled
+/// with the name of the host, to be loaded along with the shell histories.
+pub fn sources() -> Vec<Source> {
+    let Ok(entries) = store::data_dir().and_then(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut sources: Vec<Source> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let host = name
+                .strip_prefix("sync-")?
+                .strip_suffix(".json")?
+                .to_string();
+            let name = format!("synced from {}", host);
+            Some(Source::new(&host, &name, path, Format::Hui))
+        })
+        .collect();
+    sources.sort_by(|a, b| a.label.cmp(&b.label));
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for a test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = env::temp_dir().join(format!("hui-test-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn commands(runs: &[(&str, u64)]) -> Vec<Command> {
+        runs.iter()
+            .map(|(command, time)| Command::new(command.to_string(), Some(*time)))
+            .collect()
+    }
+
+    fn names(pulled: &[(String, io::Result<Vec<Command>>)]) -> Vec<(&str, Vec<&str>)> {
+        pulled
+            .iter()
+            .map(|(host, commands)| {
+                let commands = commands.as_ref().unwrap();
+                let commands = commands.iter().map(|c| c.command.as_str()).collect();
+                (host.as_str(), commands)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn machines_see_each_others_history() {
+        // Two machines syncing one directory each, like Syncthing would
+        let (laptop_dir, desktop_dir) = (TempDir::new("laptop"), TempDir::new("desktop"));
+        let copy = |from: &TempDir, to: &TempDir| {
+            for entry in fs::read_dir(&from.0).unwrap() {
+                let path = entry.unwrap().path();
+                fs::copy(&path, to.0.join(path.file_name().unwrap())).unwrap();
+            }
+        };
+        // Both ran the first command, from a history file they share
+        let mut laptop = commands(&[("vim ~/.zshrc", 50), ("make", 100), ("git push", 300)]);
+        let desktop = commands(&[("vim ~/.zshrc", 50), ("cargo test", 200)]);
+
+        assert_eq!(push(&laptop_dir.0, "laptop", &laptop, None).unwrap(), 3);
+        assert_eq!(push(&laptop_dir.0, "laptop", &laptop, None).unwrap(), 0);
+        assert_eq!(push(&desktop_dir.0, "desktop", &desktop, None).unwrap(), 2);
+        copy(&laptop_dir, &desktop_dir);
+        copy(&desktop_dir, &laptop_dir);
+
+        let on_laptop = pull(&laptop_dir.0, "laptop", &laptop, None).unwrap();
+        assert_eq!(names(&on_laptop), [("desktop", vec!["cargo test"])]);
+        let on_desktop = pull(&desktop_dir.0, "desktop", &desktop, None).unwrap();
+        assert_eq!(names(&on_desktop), [("laptop", vec!["make", "git push"])]);
+
+        // A third machine keeps a run both logs have once, from the first host
+        let devbox = pull(&laptop_dir.0, "devbox", &[], None).unwrap();
+        assert_eq!(
+            names(&devbox),
+            [
+                ("desktop", vec!["vim ~/.zshrc", "cargo test"]),
+                ("laptop", vec!["make", "git push"]),
+            ]
+        );
+
+        // Only new runs are appended, and a partial line at the end is left for later
+        laptop.extend(commands(&[("make", 400)]));
+        assert_eq!(push(&laptop_dir.0, "laptop", &laptop, None).unwrap(), 1);
+        let mut log = fs::OpenOptions::new()
+            .append(true)
+            .open(log_path(&laptop_dir.0, "laptop"))
+            .unwrap();
+        log.write_all(b"{\"command\":\"ha").unwrap();
+        assert_eq!(
+            read_log(&log_path(&laptop_dir.0, "laptop"), None)
+                .unwrap()
+                .len(),
+            4
+        );
+        assert_eq!(push(&laptop_dir.0, "laptop", &laptop, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn encrypted_logs_need_the_key() {
+        let dir = TempDir::new("encrypted");
+        let key = Key::generate();
+        let laptop = commands(&[("export TOKEN=hunter2", 100)]);
+        assert_eq!(push(&dir.0, "laptop", &laptop, Some(&key)).unwrap(), 1);
+
+        let log = fs::read_to_string(log_path(&dir.0, "laptop")).unwrap();
+        assert!(log.starts_with(ENCRYPTED));
+        assert!(!log.contains("hunter2"));
+
+        let pulled = pull(&dir.0, "desktop", &[], Some(&key)).unwrap();
+        assert_eq!(names(&pulled), [("laptop", vec!["export TOKEN=hunter2"])]);
+        for key in [None, Some(Key::generate())] {
+            let pulled = pull(&dir.0, "desktop", &[], key.as_ref()).unwrap();
+            assert!(pulled[0].1.is_err());
+        }
+    }
+
+    #[test]
+    fn damaged_logs_are_skipped() {
+        let dir = TempDir::new("damaged");
+        let laptop = commands(&[("make", 100)]);
+        let devbox = commands(&[("cargo build", 200)]);
+        push(&dir.0, "laptop", &laptop, None).unwrap();
+        push(&dir.0, "devbox", &devbox, None).unwrap();
+        fs::write(
+            log_path(&dir.0, "broken"),
+            "{\"command\":\"ls\"}\nnot json\n",
+        )
+        .unwrap();
+
+        let pulled = pull(&dir.0, "desktop", &[], None).unwrap();
+        let hosts: Vec<&str> = pulled.iter().map(|(host, _)| host.as_str()).collect();
+        assert_eq!(hosts, ["broken", "devbox", "laptop"]);
+        let err = pulled[0].1.as_ref().unwrap_err();
+        assert!(err.to_string().contains("hui-broken.log:2"), "{}", err);
+        assert_eq!(
+            names(&pulled[1..]),
+            [("devbox", vec!["cargo build"]), ("laptop", vec!["make"])]
+        );
+    }
+
+    #[test]
+    fn only_the_first_machine_creates_the_salt() {
+        let dir = TempDir::new("salt");
+        let err = passphrase_key(&dir.0, "hunter2", false).err().unwrap();
+        assert!(err.to_string().contains("no sync salt yet"), "{}", err);
+        assert!(!dir.0.join(SALT_FILE).exists());
+
+        let laptop = passphrase_key(&dir.0, "hunter2", true).unwrap();
+        assert!(passphrase_key(&dir.0, "hunter2", true).is_err());
+        let desktop = passphrase_key(&dir.0, "hunter2", false).unwrap();
+        push(&dir.0, "laptop", &commands(&[("make", 100)]), Some(&laptop)).unwrap();
+        let pulled = pull(&dir.0, "desktop", &[], Some(&desktop)).unwrap();
+        assert_eq!(names(&pulled), [("laptop", vec!["make"])]);
+    }
+
+    #[test]
+    fn host_names_are_safe_in_file_names() {
+        assert_eq!(host_name("devbox.corp\n"), "devbox.corp");
+        assert_eq!(host_name("../etc/passwd"), "_etc_passwd");
+        assert_eq!(host_name(""), "localhost");
+    }
+}
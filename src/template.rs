@@ -0,0 +1,335 @@
+//! Templates: commands with placeholders that are filled in every time
+//! they are used.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// Name of the data file templates are saved in.
+pub const FILE: &str = "templates.json";
+
+// How many previously used values are remembered for each placeholder
+const MAX_VALUES: usize = 10;
+
+/// A command with some of its words replaced by placeholders, such as
+/// `kubectl logs -n <ns> <pod>`, that are filled in every time it is used.
+#[derive(Serialize, Deserialize)]
+pub struct Template {
+    /// The text and placeholders of the command, in order.
+    pub parts: Vec<Part>,
+    /// The placeholders, in the order they first appear.
+    pub placeholders: Vec<Placeholder>,
+}
+
+/// A part of a template.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Part {
+    /// Text kept as it is.
+    Text(String),
+    /// Index into `Template::placeholders`.
+    Placeholder(usize),
+}
+
+/// A word filled in every time the template is used.
+#[derive(Serialize, Deserialize)]
+pub struct Placeholder {
+    /// The name shown as `<name>`, from the kind of word it replaced.
+    pub name: String,
+    /// Values used for this placeholder before, most recent first.
+    pub values: Vec<String>,
+}
+
+impl Template {
+    /// The command with each placeholder shown as `<name>`. This is how
+    /// templates are listed and searched alongside the history.
+    pub fn display(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Placeholder(i) => format!("<{}>", self.placeholders[*i].name),
+            })
+            .collect()
+    }
+
+    /// The command with every placeholder replaced by its value in `values`.
+    pub fn fill(&self, values: &[String]) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Placeholder(i) => values[*i].as_str(),
+            })
+            .collect()
+    }
+
+    /// Records `values` as the most recently used ones, to be suggested next time.
+    pub fn remember(&mut self, values: &[String]) {
+        for (placeholder, value) in self.placeholders.iter_mut().zip(values) {
+            placeholder.values.retain(|v| v != value);
+            placeholder.values.insert(0, value.clone());
+            placeholder.values.truncate(MAX_VALUES);
+        }
+    }
+}
+
+/// What a variable-looking word appears to be.
+#[derive(Clone, Copy)]
+enum Kind {
+    Uuid,
+    Ip,
+    Hash,
+    Number,
+    Path,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Uuid => "uuid",
+            Kind::Ip => "ip",
+            Kind::Hash => "hash",
+            Kind::Number => "number",
+            Kind::Path => "path",
+        }
+    }
+}
+
+/// A word of a command that can be turned into a placeholder.
+pub struct Token {
+    /// Where the word is in the command.
+    pub range: Range<usize>,
+    kind: Option<Kind>,
+}
+
+// What the kinds of variable-looking words look like, compiled once
+static UUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^[[:xdigit:]]{8}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{12}$",
+    )
+    .unwrap()
+});
+static IP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d{1,3}(\.\d{1,3}){3}(:\d+)?$").unwrap());
+static HASH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[[:xdigit:]]{7,}$").unwrap());
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+$").unwrap());
+static PATH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(/|~/|\./|\.\./)\S+$").unwrap());
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\S+").unwrap());
+
+/// Splits a command into the words that could become placeholders. For
+/// `--option=value` words only the value is used, and quotes are left out.
+fn tokens(command: &str) -> Vec<Token> {
+    WORD.find_iter(command)
+        .filter_map(|word| {
+            let mut range = word.range();
+            if let Some(eq) = word.as_str().find('=').filter(|eq| *eq > 0) {
+                range.start += eq + 1;
+            }
+            let text = &command[range.clone()];
+            for quote in ['"', '\''] {
+                if text.len() >= 2 && text.starts_with(quote) && text.ends_with(quote) {
+                    range = range.start + 1..range.end - 1;
+                }
+            }
+            let text = &command[range.clone()];
+            if text.is_empty() {
+                return None;
+            }
+
+            let kind = if UUID.is_match(text) {
+                Some(Kind::Uuid)
+            } else if IP.is_match(text) {
+                Some(Kind::Ip)
+            } else if HASH.is_match(text)
+                && text.chars().any(|c| c.is_ascii_digit())
+                && text.chars().any(|c| c.is_ascii_alphabetic())
+            {
+                Some(Kind::Hash)
+            } else if NUMBER.is_match(text) {
+                Some(Kind::Number)
+            } else if PATH.is_match(text) {
+                Some(Kind::Path)
+            } else {
+                None
+            };
+            Some(Token { range, kind })
+        })
+        .collect()
+}
+
+/// Choosing which words of a command become placeholders, before saving it
+/// as a template. Variable-looking words start out marked.
+pub struct Marking {
+    /// The command the template is made from.
+    pub command: String,
+    /// The words of the command that can be marked.
+    pub tokens: Vec<Token>,
+    /// Whether each token is marked.
+    pub marked: Vec<bool>,
+    /// Index of the token the cursor is on.
+    pub cursor: usize,
+}
+
+impl Marking {
+    /// Starts marking the words of `command`, on the first one marked.
+    pub fn new(command: &str) -> Marking {
+        let tokens = tokens(command);
+        let marked: Vec<bool> = tokens.iter().map(|token| token.kind.is_some()).collect();
+        let cursor = marked.iter().position(|marked| *marked).unwrap_or(0);
+        Marking {
+            command: command.to_string(),
+            tokens,
+            marked,
+            cursor,
+        }
+    }
+
+    /// Moves the cursor to the next word.
+    pub fn next(&mut self) {
+        if self.cursor + 1 < self.tokens.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Moves the cursor to the previous word.
+    pub fn previous(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    /// Marks or unmarks the word the cursor is on.
+    pub fn toggle(&mut self) {
+        if let Some(marked) = self.marked.get_mut(self.cursor) {
+            *marked = !*marked;
+        }
+    }
+
+    /// Builds the template. Marked words with the same value share a
+    /// placeholder, which starts out with that value as its suggestion.
+    pub fn template(&self) -> Template {
+        let mut parts = Vec::new();
+        let mut placeholders: Vec<Placeholder> = Vec::new();
+        let mut end = 0;
+        for (token, _) in self
+            .tokens
+            .iter()
+            .zip(&self.marked)
+            .filter(|(_, marked)| **marked)
+        {
+            let value = &self.command[token.range.clone()];
+            let index = match placeholders.iter().position(|p| p.values[0] == value) {
+                Some(index) => index,
+                None => {
+                    let kind = token.kind.map_or("arg", Kind::name);
+                    let count = placeholders
+                        .iter()
+                        .filter(|p| p.name.trim_end_matches(char::is_numeric) == kind)
+                        .count();
+                    let name = if count == 0 {
+                        kind.to_string()
+                    } else {
+                        format!("{}{}", kind, count + 1)
+                    };
+                    placeholders.push(Placeholder {
+                        name,
+                        values: vec![value.to_string()],
+                    });
+                    placeholders.len() - 1
+                }
+            };
+            if token.range.start > end {
+                parts.push(Part::Text(self.command[end..token.range.start].to_string()));
+            }
+            parts.push(Part::Placeholder(index));
+            end = token.range.end;
+        }
+        if end < self.command.len() {
+            parts.push(Part::Text(self.command[end..].to_string()));
+        }
+        Template {
+            parts,
+            placeholders,
+        }
+    }
+}
+
+/// Filling in the placeholders of a template before using it.
+pub struct Form {
+    /// Index of the template being filled in.
+    pub template: usize,
+    /// The value of each placeholder so far.
+    pub values: Vec<String>,
+    /// Index of the placeholder being filled in.
+    pub field: usize,
+    // Index of the suggestion last put in the current field
+    suggestion: usize,
+}
+
+impl Form {
+    /// Starts a form with every field set to the value used last time.
+    pub fn new(index: usize, template: &Template) -> Form {
+        Form {
+            template: index,
+            values: template
+                .placeholders
+                .iter()
+                .map(|p| p.values.first().cloned().unwrap_or_default())
+                .collect(),
+            field: 0,
+            suggestion: 0,
+        }
+    }
+
+    /// Moves to the next placeholder.
+    pub fn next_field(&mut self) {
+        if self.field + 1 < self.values.len() {
+            self.field += 1;
+            self.suggestion = 0;
+        }
+    }
+
+    /// Moves to the previous placeholder.
+    pub fn previous_field(&mut self) {
+        if self.field > 0 {
+            self.field -= 1;
+            self.suggestion = 0;
+        }
+    }
+
+    /// Types `c` at the end of the current field.
+    pub fn insert(&mut self, c: char) {
+        if let Some(value) = self.values.get_mut(self.field) {
+            value.push(c);
+        }
+    }
+
+    /// Deletes the last character of the current field.
+    pub fn backspace(&mut self) {
+        if let Some(value) = self.values.get_mut(self.field) {
+            value.pop();
+        }
+    }
+
+    /// Empties the current field.
+    pub fn clear(&mut self) {
+        if let Some(value) = self.values.get_mut(self.field) {
+            value.clear();
+        }
+    }
+
+    /// Replaces the current field with the next of its previously used values.
+    pub fn next_suggestion(&mut self, template: &Template) {
+        let Some(placeholder) = template.placeholders.get(self.field) else {
+            return;
+        };
+        if placeholder.values.is_empty() {
+            return;
+        }
+        // Skip the suggestion that is already in the field
+        if placeholder.values.get(self.suggestion) == Some(&self.values[self.field]) {
+            self.suggestion = (self.suggestion + 1) % placeholder.values.len();
+        }
+        self.values[self.field] = placeholder.values[self.suggestion].clone();
+    }
+}
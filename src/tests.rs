@@ -0,0 +1,589 @@
+//! Tests of the interface: scripted key presses and mouse clicks are fed to
+//! the app, which is drawn on ratatui's `TestBackend` after each of them.
+
+use super::*;
+use crate::config::Palette;
+use crossterm::event::{KeyModifiers, MouseEvent};
+use ratatui::backend::TestBackend;
+use regex::Regex;
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+const WIDTH: u16 = 72;
+const HEIGHT: u16 = 14;
+
+// Oldest first, as in a history file
+const HISTORY: [&str; 5] = [
+    "ls -la",
+    "git status",
+    "cargo build",
+    "git push origin main",
+    "docker run nginx",
+];
+
+/// Keeps what is copied, for the tests to check.
+#[derive(Clone, Default)]
+struct Clipboard(Arc<Mutex<String>>);
+
+impl ClipboardProvider for Clipboard {
+    fn get_contents(&mut self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+        *self.0.lock().unwrap() = contents;
+        Ok(())
+    }
+}
+
+/// A script of events, which fails once it runs out so that a test can't hang.
+impl EventSource for VecDeque<Event> {
+    fn next(&mut self, _timeout: Duration) -> io::Result<Option<Event>> {
+        match self.pop_front() {
+            Some(event) => Ok(Some(event)),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the script ran out of events",
+            )),
+        }
+    }
+}
+
+/// An app showing `history`, with the history already loaded.
+fn app(output: Output, history: &[&str]) -> (App, Clipboard) {
+    app_with_templates(output, history, Vec::new())
+}
+
+/// An app showing `templates` and `history`, with the history already loaded.
+fn app_with_templates(
+    output: Output,
+    history: &[&str],
+    templates: Vec<Template>,
+) -> (App, Clipboard) {
+    let (sender, loader) = mpsc::channel();
+    let commands = history
+        .iter()
+        .map(|command| Command::new(command.to_string(), None))
+        .collect();
+    sender.send(Loaded::Older(0, commands)).unwrap();
+    sender.send(Loaded::Done(0, Ok(()))).unwrap();
+    let config = Config {
+        scroll_lines: 1,
+        output,
+        dedup: Dedup::Latest,
+        theme: Palette::Dark,
+    };
+    let clipboard = Clipboard::default();
+    let mut app = App::new(
+        Ignore::default(),
+        Danger::default(),
+        vec!["zsh".to_string()],
+        Ok(templates),
+        Ok(Vec::new()),
+        vec![SourceStatus {
+            name: "history".to_string(),
+            loaded: Ok(0),
+        }],
+        config,
+        loader,
+        None,
+        Box::new(clipboard.clone()),
+    );
+    app.reload();
+    (app, clipboard)
+}
+
+fn print(message: &str) -> Option<Exit> {
+    Some(Exit::Print(message.to_string()))
+}
+
+fn key(code: KeyCode) -> Event {
+    Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+fn ctrl(c: char) -> Event {
+    Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+}
+
+fn click(column: u16, row: u16) -> Event {
+    Event::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column,
+        row,
+        modifiers: KeyModifiers::NONE,
+    })
+}
+
+/// Feeds events to an app one at a time, the way `run_app` does, and draws
+/// it after each of them.
+struct Harness {
+    terminal: Terminal<TestBackend>,
+    app: App,
+    clipboard: Clipboard,
+    // How the app exited, once an event made it exit
+    exit: Option<Exit>,
+}
+
+impl Harness {
+    fn new(output: Output) -> Harness {
+        Harness::with_history(output, &HISTORY)
+    }
+
+    fn with_history(output: Output, history: &[&str]) -> Harness {
+        Harness::with_app(app(output, history))
+    }
+
+    fn with_app((app, clipboard): (App, Clipboard)) -> Harness {
+        let mut harness = Harness {
+            terminal: Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap(),
+            app,
+            clipboard,
+            exit: None,
+        };
+        harness.draw();
+        harness
+    }
+
+    fn send(&mut self, event: Event) {
+        assert!(self.exit.is_none(), "the app has already exited");
+        self.exit = self.app.handle_event(event);
+        // The search is applied on the next tick
+        self.app.on_tick();
+        self.draw();
+    }
+
+    fn press(&mut self, code: KeyCode) {
+        self.send(key(code));
+    }
+
+    fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.press(KeyCode::Char(c));
+        }
+    }
+
+    fn draw(&mut self) {
+        self.terminal.draw(|f| ui(f, &mut self.app)).unwrap();
+    }
+
+    /// The lines on screen, with how long the search took left out.
+    fn screen(&self) -> Vec<String> {
+        let timing = Regex::new(r"\d+\.\d ms").unwrap();
+        let buffer = self.terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| {
+                let line: String = (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect();
+                timing.replace(line.trim_end(), "_ ms").into_owned()
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn starts_with_the_most_recent_command_first() {
+    let harness = Harness::new(Output::Stdout);
+    assert_eq!(
+        harness.screen(),
+        [
+            "",
+            " ┌ hui v0.2.1 ────────────────────────────────────────────────────────┐",
+            " │> 1 docker run nginx                                                │",
+            " │  2 git push origin main                                            │",
+            " │  3 cargo build                                                     │",
+            " │  4 git status                                                      │",
+            " │  5 ls -la                                                          │",
+            " └────────────────────────────────────────────────────────────────────┘",
+            " ┌ search ────────────────────────────────────────────────────────────┐",
+            " │                                                                    │",
+            " └────────────────────────────────────────────────────────────────────┘",
+            " 5/5 │ fuzzy │ sort: relevance │ scope: all │ _ ms │ history (5)",
+            " Press / to filter results, Enter to copy selected command and exit, q",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn filters_navigates_and_accepts() {
+    let mut harness = Harness::new(Output::Stdout);
+    harness.press(KeyCode::Char('/'));
+    harness.type_text("git");
+    assert_eq!(
+        harness.screen(),
+        [
+            "",
+            " ┌ hui v0.2.1 ────────────────────────────────────────────────────────┐",
+            " │  1 git push origin main                                            │",
+            " │  2 git status                                                      │",
+            " │                                                                    │",
+            " │                                                                    │",
+            " │                                                                    │",
+            " └────────────────────────────────────────────────────────────────────┘",
+            " ┌ search ────────────────────────────────────────────────────────────┐",
+            " │git                                                                 │",
+            " └────────────────────────────────────────────────────────────────────┘",
+            " 2/5 │ fuzzy │ sort: relevance │ scope: all │ _ ms │ history (5)",
+            " Press Enter to filter history, Esc to stop filtering, F1 for help.",
+            "",
+        ]
+    );
+
+    harness.press(KeyCode::Enter);
+    harness.press(KeyCode::Down);
+    assert_eq!(
+        &harness.screen()[2..4],
+        [
+            " │  1 git push origin main                                            │",
+            " │> 2 git status                                                      │",
+        ]
+    );
+    harness.press(KeyCode::Enter);
+    assert_eq!(harness.exit, print("git status"));
+}
+
+#[test]
+fn highlights_commands_and_what_the_search_matched() {
+    let mut harness = Harness::new(Output::Stdout);
+    harness.press(KeyCode::Char('/'));
+    harness.type_text("push");
+    assert!(harness.screen()[2].starts_with(" │  1 git push origin main  "));
+    let cell = |x| harness.terminal.backend().buffer().get(x, 2).clone();
+    // `git` is the command, `push` its subcommand and what was searched for
+    assert_eq!(cell(6).fg, Color::Green);
+    assert!(!cell(6).modifier.contains(Modifier::UNDERLINED));
+    assert_eq!(cell(10).fg, Color::Cyan);
+    assert!(cell(10).modifier.contains(Modifier::UNDERLINED));
+    assert_eq!(cell(15).fg, Color::Reset);
+}
+
+#[test]
+fn esc_resets_the_search() {
+    let mut harness = Harness::new(Output::Stdout);
+    harness.press(KeyCode::Char('/'));
+    harness.type_text("cargo");
+    assert_eq!(harness.app.items.items().len(), 1);
+
+    harness.press(KeyCode::Esc);
+    assert_eq!(harness.app.input, "");
+    assert_eq!(harness.app.items.items().len(), HISTORY.len());
+    assert_eq!(harness.screen()[9], format!(" │{:68}│", ""));
+    // Back in the list, so keys move the selection again
+    harness.press(KeyCode::Down);
+    harness.press(KeyCode::Enter);
+    assert_eq!(harness.exit, print("git push origin main"));
+}
+
+#[test]
+fn ctrl_w_deletes_the_last_word() {
+    let mut harness = Harness::new(Output::Stdout);
+    harness.press(KeyCode::Char('/'));
+    harness.type_text("git push");
+    assert_eq!(harness.app.items.items().len(), 1);
+
+    harness.send(ctrl('w'));
+    assert_eq!(harness.app.input, "git ");
+    assert!(harness.screen()[9].starts_with(" │git  "));
+    assert_eq!(harness.app.items.items().len(), 2);
+
+    harness.send(ctrl('w'));
+    assert_eq!(harness.app.input, "");
+    assert_eq!(harness.app.items.items().len(), HISTORY.len());
+}
+
+#[test]
+fn clicking_the_search_box_starts_a_search() {
+    let mut harness = Harness::new(Output::Stdout);
+    harness.send(click(10, 9));
+    harness.type_text("docker");
+    assert!(harness.screen()[9].starts_with(" │docker  "));
+    assert_eq!(harness.app.items.items().len(), 1);
+
+    // Clicking the list goes back to it, selecting the entry clicked
+    harness.send(click(10, 2));
+    harness.press(KeyCode::Enter);
+    assert_eq!(harness.exit, print("docker run nginx"));
+}
+
+#[test]
+fn clicking_an_entry_selects_it_and_a_double_click_accepts_it() {
+    let mut harness = Harness::new(Output::Stdout);
+    harness.send(click(10, 4));
+    assert!(harness.screen()[4].starts_with(" │> 3 cargo build  "));
+    assert!(harness.exit.is_none());
+
+    harness.send(click(10, 4));
+    assert_eq!(harness.exit, print("cargo build"));
+}
+
+#[test]
+fn clicking_a_list_too_small_to_draw_does_nothing() {
+    let (mut app, _) = app(Output::Stdout, &HISTORY);
+    app.chunks = Rc::new([Rect::new(0, 0, 0, 0)]);
+    assert_eq!(app.item_at(5, 5), None);
+    app.chunks = Rc::new([Rect::new(0, 0, 10, 0)]);
+    assert_eq!(app.item_at(5, 5), None);
+}
+
+#[test]
+fn accepting_copies_to_the_clipboard() {
+    let mut harness = Harness::new(Output::Clipboard);
+    harness.press(KeyCode::Down);
+    harness.press(KeyCode::Enter);
+    assert_eq!(
+        harness.clipboard.0.lock().unwrap().as_str(),
+        "git push origin main"
+    );
+    assert_eq!(
+        harness.exit,
+        print("Copied to clipboard: git push origin main")
+    );
+}
+
+#[test]
+fn only_templates_open_the_template_form() {
+    let template = Template {
+        parts: vec![
+            template::Part::Text("ssh ".to_string()),
+            template::Part::Placeholder(0),
+        ],
+        placeholders: vec![template::Placeholder {
+            name: "host".to_string(),
+            values: Vec::new(),
+        }],
+    };
+    // A command that was run looking just like the template
+    let mut harness = Harness::with_app(app_with_templates(
+        Output::Stdout,
+        &["ssh <host>"],
+        vec![template],
+    ));
+    assert!(harness.screen()[2].starts_with(" │> 1 [template] ssh <host>  "));
+    assert!(harness.screen()[3].starts_with(" │  2 ssh <host>  "));
+
+    harness.press(KeyCode::Enter);
+    assert!(harness.app.form.is_some());
+    assert!(harness.exit.is_none());
+
+    harness.press(KeyCode::Esc);
+    harness.press(KeyCode::Down);
+    harness.press(KeyCode::Enter);
+    assert!(harness.app.form.is_none());
+    assert_eq!(harness.exit, print("ssh <host>"));
+}
+
+#[test]
+fn runs_a_script_of_events() {
+    let (app, _) = app(Output::Stdout, &HISTORY);
+    let mut terminal = Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap();
+    let mut script: VecDeque<Event> = [
+        key(KeyCode::Char('/')),
+        key(KeyCode::Char('l')),
+        key(KeyCode::Char('s')),
+        key(KeyCode::Enter),
+        key(KeyCode::Enter),
+    ]
+    .into();
+    let exit = run_app(&mut terminal, app, Duration::ZERO, &mut script).unwrap();
+    assert_eq!(exit, Exit::Print("ls -la".to_string()));
+
+    let (app, _) = self::app(Output::Stdout, &HISTORY);
+    let mut script: VecDeque<Event> = [key(KeyCode::Char('q'))].into();
+    let exit = run_app(&mut terminal, app, Duration::ZERO, &mut script).unwrap();
+    assert_eq!(exit, Exit::Print("".to_string()));
+}
+
+#[test]
+fn running_a_dangerous_command_asks_first() {
+    let history = ["ls -la", "rm -rf build"];
+    let mut harness = Harness::with_history(Output::Stdout, &history);
+    assert!(harness.screen()[2].starts_with(" │> 1 ⚠ rm -rf build  "));
+    assert!(harness.screen()[3].starts_with(" │  2 ls -la  "));
+
+    harness.press(KeyCode::Char('r'));
+    assert!(harness.exit.is_none());
+    let confirming = |harness: &Harness| {
+        harness
+            .screen()
+            .iter()
+            .any(|line| line.contains(" dangerous command "))
+    };
+    assert!(confirming(&harness));
+    harness.press(KeyCode::Char('n'));
+    assert!(!confirming(&harness));
+
+    // Safe commands run right away
+    harness.press(KeyCode::Down);
+    harness.press(KeyCode::Char('r'));
+    assert_eq!(harness.exit, Some(Exit::Run("ls -la".to_string())));
+
+    let mut harness = Harness::with_history(Output::Stdout, &history);
+    harness.press(KeyCode::Char('r'));
+    harness.press(KeyCode::Char('y'));
+    assert_eq!(harness.exit, Some(Exit::Run("rm -rf build".to_string())));
+}
+
+#[test]
+fn explains_the_selected_command() {
+    let mut harness = Harness::with_history(Output::Stdout, &["ls -la", "frob --now"]);
+    harness.terminal = Terminal::new(TestBackend::new(WIDTH, 24)).unwrap();
+    harness.app.explainer = Explainer::new(|name| {
+        match name {
+        "ls" => Some(hui::explain::parse_roff(
+            ".SH NAME\nls \\- list directory contents\n.SH DESCRIPTION\n.TP\n\\fB\\-a\\fR, \\fB\\-\\-all\\fR\n\
+             do not ignore entries starting with .\n.TP\n\\fB\\-l\\fR\nuse a long listing format\n",
+        )),
+        _ => None,
+    }
+    });
+    // Waits for the pages to be read, which happens on a thread of its own
+    let explained = |harness: &mut Harness| {
+        for _ in 0..500 {
+            harness.draw();
+            if !harness
+                .screen()
+                .iter()
+                .any(|line| line.contains("Reading man pages"))
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let screen = harness.screen();
+        let panel = screen.iter().position(|line| line.contains(" explain "));
+        screen[panel.expect("the explain panel is shown")..].to_vec()
+    };
+
+    harness.press(KeyCode::Char('x'));
+    let screen = explained(&mut harness);
+    assert!(screen[0].starts_with(" ┌ explain ─"));
+    assert!(screen[1].starts_with(" │frob --now "));
+    assert!(screen[3].starts_with(" │frob — no man page or tldr page found "));
+    assert!(screen[4].starts_with(" │  --now "));
+
+    harness.press(KeyCode::Down);
+    let screen = explained(&mut harness);
+    assert!(screen[1].starts_with(" │ls -la "));
+    assert!(screen[3].starts_with(" │ls — list directory contents "));
+    assert!(screen[4].starts_with(" │  -l  use a long listing format "));
+    assert!(screen[5].starts_with(" │  -a  do not ignore entries starting with . "));
+
+    harness.press(KeyCode::Char('x'));
+    assert!(!harness
+        .screen()
+        .iter()
+        .any(|line| line.contains(" explain ")));
+}
+
+#[test]
+fn groups_entries_by_program_and_subcommand() {
+    let history = [
+        "docker run nginx",
+        "git status",
+        "docker run -it alpine sh",
+        "ls -la",
+        "docker run redis",
+        "docker ps",
+    ];
+    let mut harness = Harness::with_history(Output::Stdout, &history);
+    harness.press(KeyCode::Char('v'));
+    assert_eq!(
+        &harness.screen()[2..7],
+        [
+            " │> 1 docker ps                                                       │",
+            " │  2 ▸ docker run  (3)                                               │",
+            " │  3 ls -la                                                          │",
+            " │  4 git status                                                      │",
+            " │                                                                    │",
+        ]
+    );
+    assert!(harness.screen()[11]
+        .starts_with(" 6/6 │ fuzzy │ sort: relevance │ scope: all │ 4 groups │"));
+
+    harness.press(KeyCode::Down);
+    harness.press(KeyCode::Right);
+    assert_eq!(
+        &harness.screen()[2..7],
+        [
+            " │  1 docker ps                                                       │",
+            " │> 2 ▾ docker run  (3)                                               │",
+            " │  3   docker run redis                                              │",
+            " │  4   docker run -it alpine sh                                      │",
+            " │  5   docker run nginx                                              │",
+        ]
+    );
+
+    // Searching filters within the groups
+    harness.press(KeyCode::Char('/'));
+    harness.type_text("run n");
+    harness.press(KeyCode::Enter);
+    let screen = harness.screen();
+    assert_eq!(
+        screen[2],
+        " │> 1 ▾ docker run  (2)                                               │"
+    );
+    assert!(screen[3..5]
+        .iter()
+        .all(|line| line.starts_with(" │  2   docker run ")
+            || line.starts_with(" │  3   docker run ")));
+    assert!(screen[3..5].iter().all(|line| !line.contains("redis")));
+
+    // Left collapses the group of an entry, and Enter on a group toggles it
+    harness.press(KeyCode::Down);
+    harness.press(KeyCode::Left);
+    assert_eq!(
+        &harness.screen()[2..4],
+        [
+            " │> 1 ▸ docker run  (2)                                               │",
+            " │                                                                    │",
+        ]
+    );
+    harness.press(KeyCode::Enter);
+    assert!(harness.exit.is_none());
+    harness.press(KeyCode::Down);
+    harness.press(KeyCode::Char('e'));
+    assert!(harness.app.editor.is_some());
+}
+
+#[test]
+fn narrows_the_list_to_when_commands_were_run() {
+    let mut harness = Harness::new(Output::Stdout);
+    let now = Local::now();
+    for entry in &mut harness.app.history {
+        let ago = if entry.command.starts_with("git") {
+            0
+        } else {
+            30
+        };
+        entry.timestamp = Some((now - chrono::Duration::days(ago)).timestamp() as u64);
+    }
+    harness.press(KeyCode::Char('/'));
+    harness.type_text("@today");
+    let commands = |harness: &Harness| -> Vec<String> {
+        let items = harness.app.items.items();
+        items.iter().map(|item| item.command.clone()).collect()
+    };
+    assert_eq!(commands(&harness), ["git push origin main", "git status"]);
+    let today = now.format("%Y-%m-%d");
+    assert_eq!(
+        harness.screen()[11],
+        format!(
+            " 2/5 │ fuzzy │ sort: relevance │ scope: all │ time: {} │ _ ms",
+            today
+        )
+    );
+
+    // The rest of the query still searches, and is what is highlighted
+    harness.type_text(" stat");
+    assert_eq!(commands(&harness), ["git status"]);
+    assert_eq!(harness.app.query, "stat");
+
+    harness.send(ctrl('u'));
+    harness.type_text("@>1w");
+    assert_eq!(
+        commands(&harness),
+        ["docker run nginx", "cargo build", "ls -la"]
+    );
+}
@@ -0,0 +1,108 @@
+use crate::config::Palette;
+use hui::shell::{self, Kind};
+use ratatui::style::{Color, Modifier, Style};
+use std::ops::Range;
+
+/// The styles the parts of a command are drawn with.
+pub struct Theme {
+    command: Style,
+    subcommand: Style,
+    flag: Style,
+    string: Style,
+    variable: Style,
+    operator: Style,
+    comment: Style,
+    /// Added on top of the style of the parts the search matched.
+    matched: Style,
+}
+
+impl Theme {
+    pub fn new(palette: Palette) -> Theme {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let italic = Style::default().add_modifier(Modifier::ITALIC);
+        let matched = Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        match palette {
+            Palette::Dark => Theme {
+                command: bold.fg(Color::Green),
+                subcommand: Style::default().fg(Color::Cyan),
+                flag: Style::default().fg(Color::Yellow),
+                string: Style::default().fg(Color::LightMagenta),
+                variable: Style::default().fg(Color::LightBlue),
+                operator: bold,
+                comment: italic.fg(Color::DarkGray),
+                matched,
+            },
+            Palette::Light => Theme {
+                command: bold.fg(Color::Green),
+                subcommand: Style::default().fg(Color::Blue),
+                flag: Style::default().fg(Color::Magenta),
+                string: Style::default().fg(Color::Cyan),
+                variable: italic.fg(Color::Blue),
+                operator: bold,
+                comment: italic.fg(Color::DarkGray),
+                matched,
+            },
+            Palette::Plain => Theme {
+                command: bold,
+                subcommand: Style::default(),
+                flag: Style::default(),
+                string: Style::default(),
+                variable: Style::default(),
+                operator: Style::default(),
+                comment: italic,
+                matched,
+            },
+        }
+    }
+
+    pub fn style(&self, kind: Kind) -> Style {
+        match kind {
+            Kind::Command => self.command,
+            Kind::Subcommand => self.subcommand,
+            Kind::Flag => self.flag,
+            Kind::String => self.string,
+            Kind::Variable => self.variable,
+            Kind::Operator => self.operator,
+            Kind::Comment => self.comment,
+        }
+    }
+
+    /// Splits `command` into pieces styled by what they are, with the parts
+    /// in `matches` (byte ranges, in order) highlighted on top of that.
+    pub fn highlight<'a>(
+        &self,
+        command: &'a str,
+        matches: &[Range<usize>],
+    ) -> Vec<(&'a str, Style)> {
+        let tokens = shell::tokens(command);
+        // A piece starts wherever a token or a match starts or ends
+        let mut bounds: Vec<usize> = tokens
+            .iter()
+            .map(|token| &token.range)
+            .chain(matches)
+            .flat_map(|range| [range.start, range.end])
+            .chain([0, command.len()])
+            .collect();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let mut tokens = tokens.iter().peekable();
+        let mut matches = matches.iter().peekable();
+        bounds
+            .windows(2)
+            .map(|piece| {
+                let (start, end) = (piece[0], piece[1]);
+                while tokens.next_if(|token| token.range.end <= start).is_some() {}
+                while matches.next_if(|range| range.end <= start).is_some() {}
+                let mut style = match tokens.peek() {
+                    Some(token) if token.range.start <= start => self.style(token.kind),
+                    _ => Style::default(),
+                };
+                if matches.peek().is_some_and(|range| range.start <= start) {
+                    style = style.patch(self.matched);
+                }
+                (&command[start..end], style)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,140 @@
+//! Watching history files, to read what is added to them while hui is
+//! open.
+
+use crate::history::{Command, HistorySource};
+use notify::event::ModifyKind;
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+// How often the files are checked when they can't be watched with inotify
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What changed in a history source.
+pub enum Update {
+    /// Commands were added at the end of the file.
+    Appended(Vec<Command>),
+    /// The file was rewritten or replaced, and this is everything it now holds.
+    Replaced(io::Result<Vec<Command>>),
+}
+
+/// Watches the history sources, so that commands run while hui is open show
+/// up in it. Files that are only appended to are read from where they were
+/// read up to, so only the new commands are parsed.
+pub struct Watch {
+    sources: Vec<Arc<dyn HistorySource>>,
+    // Where each source is, as reported by the watcher, and how much of it has been read
+    paths: Vec<Option<PathBuf>>,
+    lengths: Vec<u64>,
+    events: Receiver<notify::Result<Event>>,
+    // Watching stops when this is dropped
+    _watcher: Box<dyn Watcher>,
+}
+
+impl Watch {
+    /// Starts watching `sources`, of which the first `lengths` bytes are read.
+    /// This uses inotify (or its equivalent) when it can and checks the files
+    /// every second otherwise. Sources without a file aren't watched.
+    pub fn new(sources: Vec<Arc<dyn HistorySource>>, lengths: Vec<u64>) -> notify::Result<Watch> {
+        // The directories are watched rather than the files, so that a file
+        // being replaced or created is seen too
+        let paths: Vec<Option<PathBuf>> = sources
+            .iter()
+            .map(|source| {
+                let path = source.path()?;
+                let dir = match path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => ".".as_ref(),
+                };
+                Some(fs::canonicalize(dir).ok()?.join(path.file_name()?))
+            })
+            .collect();
+        let dirs: Vec<PathBuf> = paths
+            .iter()
+            .flatten()
+            .filter_map(|path| path.parent().map(PathBuf::from))
+            .collect();
+
+        let (sender, events) = mpsc::channel();
+        let watcher = match RecommendedWatcher::new(sender.clone(), Config::default())
+            .and_then(|watcher| watch_all(watcher, &dirs))
+        {
+            Ok(watcher) => watcher,
+            Err(_) => {
+                let config = Config::default().with_poll_interval(POLL_INTERVAL);
+                watch_all(PollWatcher::new(sender, config)?, &dirs)?
+            }
+        };
+        Ok(Watch {
+            sources,
+            paths,
+            lengths,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Reads what changed in the sources since the last call, without waiting.
+    /// Returns the index of each source that changed with what changed in it.
+    pub fn updates(&mut self) -> Vec<(usize, Update)> {
+        // Whether each changed source was replaced, rather than appended to
+        let mut changed: HashMap<usize, bool> = HashMap::new();
+        for event in self.events.try_iter().flatten() {
+            let replaced = matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            );
+            for path in &event.paths {
+                if let Some(source) = self.paths.iter().position(|p| p.as_ref() == Some(path)) {
+                    *changed.entry(source).or_default() |= replaced;
+                }
+            }
+        }
+
+        let mut changed: Vec<(usize, bool)> = changed.into_iter().collect();
+        changed.sort();
+        changed
+            .into_iter()
+            .filter_map(|(source, replaced)| self.read(source, replaced))
+            .collect()
+    }
+
+    /// Reads the changes to `source`, or nothing when there are no new commands in it.
+    fn read(&mut self, source: usize, replaced: bool) -> Option<(usize, Update)> {
+        if !replaced {
+            match self.sources[source].read_appended(self.lengths[source]) {
+                Ok(Some((commands, len))) => {
+                    self.lengths[source] = len;
+                    if commands.is_empty() {
+                        return None;
+                    }
+                    return Some((source, Update::Appended(commands)));
+                }
+                Ok(None) => {}
+                Err(err) => return Some((source, Update::Replaced(Err(err)))),
+            }
+        }
+        self.lengths[source] = self.paths[source]
+            .as_ref()
+            .and_then(|path| fs::metadata(path).ok())
+            .map_or(0, |metadata| metadata.len());
+        Some((source, Update::Replaced(self.sources[source].read())))
+    }
+}
+
+fn watch_all<W: Watcher + 'static>(
+    mut watcher: W,
+    dirs: &[PathBuf],
+) -> notify::Result<Box<dyn Watcher>> {
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+    Ok(Box::new(watcher))
+}
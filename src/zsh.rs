@@ -0,0 +1,274 @@
+//! The zsh history file format, read and written one record at a time the
+//! way zsh itself does it.
+//!
+//! A record is a command, which with extended history starts with when it
+//! was run and how many seconds it took:
+//!
+//! ```text
+//! : 1330648651:12;make test
+//! ```
+//!
+//! Every line but the last of a multiline command ends with a backslash, and
+//! the bytes that aren't safe in zsh's own strings are metafied: escaped by a
+//! Meta byte and XOR-ed with 32.
+
+use crate::history::Command;
+
+// The byte zsh escapes the other bytes with
+const META: u8 = 0x83;
+
+// zsh stores NUL and the bytes from Meta up to Marker escaped by a Meta byte
+fn is_meta(byte: u8) -> bool {
+    byte == 0 || (META..=0xa2).contains(&byte)
+}
+
+/// Escapes the bytes zsh escapes when writing its history file.
+pub fn metafy(bytes: &[u8]) -> Vec<u8> {
+    let mut metafied = Vec::with_capacity(bytes.len());
+    for &byte in bytes {
+        if is_meta(byte) {
+            metafied.push(META);
+            metafied.push(byte ^ 32);
+        } else {
+            metafied.push(byte);
+        }
+    }
+    metafied
+}
+
+/// Undoes `metafy`, giving the bytes of a line of a zsh history file as they
+/// were typed. A Meta byte at the very end is kept as it is.
+pub fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut unmetafied = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(&byte) = bytes.next() {
+        match (byte, bytes.as_slice().first()) {
+            (META, Some(&next)) => {
+                unmetafied.push(next ^ 32);
+                bytes.next();
+            }
+            _ => unmetafied.push(byte),
+        }
+    }
+    unmetafied
+}
+
+/// Whether a line of a zsh history file, without its line break, goes on on
+/// the next line.
+pub fn continues(line: &[u8]) -> bool {
+    unmetafy(line).ends_with(b"\\")
+}
+
+/// Turns the lines of a zsh history file into commands, one line at a time.
+#[derive(Default)]
+pub struct Decoder {
+    // The command whose last line ended with a backslash
+    pending: Option<Command>,
+}
+
+impl Decoder {
+    /// A decoder that hasn't read any lines yet.
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    /// Reads a line, without its line break, and returns the command it
+    /// completes if it does.
+    pub fn line(&mut self, line: &[u8]) -> Option<Command> {
+        let line = unmetafy(line);
+        let line = String::from_utf8_lossy(&line);
+        let (text, continues) = match line.strip_suffix('\\') {
+            Some(text) => (text, true),
+            None => (&*line, false),
+        };
+        let mut command = match self.pending.take() {
+            Some(mut command) => {
+                command.command.push_str(text);
+                command
+            }
+            None => record(text),
+        };
+        if continues {
+            command.command.push('\n');
+            self.pending = Some(command);
+            return None;
+        }
+        // zsh leaves out the spaces after a backslash that ends a command
+        // (see `encode`)
+        let trimmed = command.command.trim_end_matches(' ');
+        if trimmed.len() < command.command.len() && trimmed.ends_with('\\') {
+            command.command.truncate(trimmed.len());
+        }
+        Some(command)
+    }
+
+    /// Returns the command the file ended in the middle of, if it did.
+    pub fn finish(self) -> Option<Command> {
+        self.pending
+    }
+}
+
+/// The command a record starts with, from the first line of the record.
+fn record(line: &str) -> Command {
+    match metadata(line) {
+        Some((timestamp, duration, command)) => {
+            let mut command = Command::new(command.to_string(), Some(timestamp));
+            command.context.duration = duration;
+            command
+        }
+        // Without extended history, a command starting with a colon is escaped
+        None => match line.strip_prefix('\\').filter(|rest| rest.starts_with(':')) {
+            Some(command) => Command::new(command.to_string(), None),
+            None => Command::new(line.to_string(), None),
+        },
+    }
+}
+
+/// Splits the `: 1330648651:12;` zsh puts before a command into the timestamp,
+/// the duration in milliseconds and the command, when the line starts with
+/// one. A duration of zero is what zsh writes when it didn't time the command.
+fn metadata(line: &str) -> Option<(u64, Option<u64>, &str)> {
+    let rest = line.strip_prefix(": ")?;
+    let (timestamp, rest) = rest.split_once(':')?;
+    let (duration, command) = rest.split_once(';')?;
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(timestamp) || !digits(duration) {
+        return None;
+    }
+    let timestamp = timestamp.parse().ok()?;
+    let duration = duration
+        .parse::<u64>()
+        .ok()
+        .filter(|&seconds| seconds > 0)
+        .and_then(|seconds| seconds.checked_mul(1000));
+    Some((timestamp, duration, command))
+}
+
+/// Writes a command as a record of a zsh history file, line break included,
+/// in the extended format when its timestamp is known.
+pub fn encode(command: &Command) -> Vec<u8> {
+    let mut record = String::with_capacity(command.command.len() + 24);
+    match command.timestamp {
+        Some(timestamp) => {
+            let seconds = command.context.duration.map_or(0, |ms| ms / 1000);
+            record.push_str(&format!(": {}:{};", timestamp, seconds));
+        }
+        None if command.command.starts_with(':') => record.push('\\'),
+        None => {}
+    }
+    record.push_str(&command.command.replace('\n', "\\\n"));
+    // A backslash at the end would join the next record to this one
+    if command.command.ends_with('\\') {
+        record.push(' ');
+    }
+    record.push('\n');
+    metafy(record.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn decode(contents: &[u8]) -> Vec<Command> {
+        let mut decoder = Decoder::new();
+        let mut commands: Vec<Command> = contents
+            .split_inclusive(|&byte| byte == b'\n')
+            .filter_map(|line| decoder.line(line.strip_suffix(b"\n").unwrap_or(line)))
+            .collect();
+        commands.extend(decoder.finish());
+        commands
+    }
+
+    fn encode_all(commands: &[Command]) -> Vec<u8> {
+        commands.iter().flat_map(encode).collect()
+    }
+
+    /// Commands heavy in what is special to zsh: backslashes, line breaks,
+    /// colons, spaces, Meta bytes and anything else.
+    fn command() -> impl Strategy<Value = Command> {
+        let text = prop::collection::vec(
+            prop_oneof![
+                Just('\\'),
+                Just('\n'),
+                Just(':'),
+                Just(';'),
+                Just(' '),
+                Just('\0'),
+                Just('\u{83}'),
+                Just('ö'),
+                any::<char>(),
+            ],
+            0..24,
+        )
+        .prop_map(|chars| chars.into_iter().collect::<String>());
+        let time = prop::option::of((any::<u64>(), 0..u64::MAX / 1000));
+        (text, time).prop_map(|(text, time)| {
+            let mut command = Command::new(text, time.map(|(timestamp, _)| timestamp));
+            command.context.duration = time.map(|(_, seconds)| seconds * 1000).filter(|&ms| ms > 0);
+            command
+        })
+    }
+
+    /// What zsh itself can't tell apart: a command that starts with `\:`
+    /// reads as one starting with `:`, and the spaces after a backslash that
+    /// ends a command are dropped.
+    fn round_trips(command: &Command) -> bool {
+        let text = &command.command;
+        let trimmed = text.trim_end_matches(' ');
+        let escaped = command.timestamp.is_none() && text.starts_with("\\:");
+        let spaced = trimmed.len() < text.len() && trimmed.ends_with('\\');
+        !escaped && !spaced
+    }
+
+    proptest! {
+        #[test]
+        fn encoding_round_trips(commands in prop::collection::vec(command(), 0..8)) {
+            let commands: Vec<Command> = commands.into_iter().filter(round_trips).collect();
+            prop_assert_eq!(decode(&encode_all(&commands)), commands);
+        }
+
+        #[test]
+        fn decoding_anything_is_stable(contents in prop::collection::vec(any::<u8>(), 0..256)) {
+            let commands = decode(&contents);
+            prop_assert_eq!(decode(&encode_all(&commands)), commands);
+        }
+    }
+
+    #[test]
+    fn reads_durations_of_any_length() {
+        let commands = decode(b": 1700000000:12;make test\n: 1700000001:0;ls\n");
+        assert_eq!(commands[0].command, "make test");
+        assert_eq!(commands[0].context.duration, Some(12_000));
+        assert_eq!(commands[1].command, "ls");
+        assert_eq!(commands[1].context.duration, None);
+    }
+
+    #[test]
+    fn keeps_what_only_looks_like_a_new_record() {
+        let command = Command::new("printf 'a\\n: 1:0;b'\necho\n: 2:0;c".to_string(), Some(1));
+        assert_eq!(decode(&encode(&command)), [command]);
+    }
+
+    #[test]
+    fn reads_files_ending_anywhere() {
+        assert!(decode(b"").is_empty());
+        assert_eq!(decode(b"echo a\x83")[0].command, "echo a\u{fffd}");
+        assert_eq!(decode(b"echo \\")[0].command, "echo \n");
+    }
+
+    #[test]
+    fn escapes_what_zsh_would_misread() {
+        let command = Command::new(": not metadata \\".to_string(), None);
+        assert_eq!(encode(&command), b"\\: not metadata \\ \n");
+        assert_eq!(decode(&encode(&command)), [command]);
+    }
+
+    #[test]
+    fn unmetafies_every_meta_byte() {
+        let metafied = metafy("ÖÖ Öl\0".as_bytes());
+        assert_eq!(unmetafy(&metafied), "ÖÖ Öl\0".as_bytes());
+        // A Meta byte at the very end has nothing to unmetafy
+        assert_eq!(unmetafy(b"a\x83"), b"a\x83");
+    }
+}